@@ -0,0 +1,59 @@
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+
+use esh::command::parser::{parse_statements, tokenise};
+
+/// A script of at least `lines` lines, made of the sorts of statements found in real ones
+fn script(lines: usize) -> String {
+    let chunk = [
+        "function area(shape) {",
+        "    if shape.kind == 'circle' {",
+        "        return 3.14159 * shape.radius ^ 2",
+        "    } else if shape.kind == 'rect' {",
+        "        return shape.width * shape.height",
+        "    }",
+        "    0",
+        "}",
+        "shapes = { { kind: 'circle', radius: 2 }, { kind: 'rect', width: 3, height: (4 + 1) * 2 } }",
+        "total = 0",
+        "for shape in shapes { total = total + area(shape) }",
+        "sizes = shapes | map(shape -> { name: shape.kind, area: area(shape) }) | filter(i -> i.area > 10 && !(i.name == 'rect'))",
+        "scale = a; b -> a * b + -1",
+        "x = 1; y = x; x; y; z = x; y -> x + y",
+        "try { sizes.0.area } catch err { print(err.message) }",
+        "ls('-l', all: true) | grep('esh') | head(lines: 5)",
+        "// The sizes are written out once they're known",
+        "print(f(g(h(1, 2), 3)).x.0, index.(1 + 0).'content-type')",
+    ];
+
+    chunk.repeat(lines.div_ceil(chunk.len())).join("\n")
+}
+
+fn bench_parse(c: &mut Criterion) {
+    let source = script(10_000);
+    let tokens = tokenise(&source).unwrap();
+
+    let mut group = c.benchmark_group("tokenise");
+    group.throughput(Throughput::Bytes(source.len() as u64));
+    group.bench_function("10k lines", |b| b.iter(|| tokenise(std::hint::black_box(&source)).unwrap()));
+    group.finish();
+
+    // Tokenising is left out of the rest, so that only the parser is measured
+    let mut group = c.benchmark_group("parse");
+    group.throughput(Throughput::Elements(tokens.len() as u64));
+    group.bench_function("10k lines", |b| b.iter(|| parse_statements(std::hint::black_box(&tokens)).unwrap()));
+
+    // Deeply nested brackets were what made the old parser quadratic
+    let nested = tokenise(&format!("{}1{}", "(1 + ".repeat(500), ")".repeat(500))).unwrap();
+    group.throughput(Throughput::Elements(nested.len() as u64));
+    group.bench_function("500 nested parentheses", |b| b.iter(|| parse_statements(std::hint::black_box(&nested)).unwrap()));
+
+    // Each semicolon might have begun the parameters of a lambda, which used to be checked by reading to the chain's end
+    let semicolons = tokenise(&"x; ".repeat(40_000)).unwrap();
+    group.throughput(Throughput::Elements(semicolons.len() as u64));
+    group.bench_function("40k statements on one line", |b| b.iter(|| parse_statements(std::hint::black_box(&semicolons)).unwrap()));
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_parse);
+criterion_main!(benches);
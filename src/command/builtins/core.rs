@@ -0,0 +1,61 @@
+use std::io::Write;
+
+use futures::StreamExt;
+
+use crate::command::builtins::{Arguments, Builtin, BuiltinResult};
+use crate::command::runtime_err::RuntimeError;
+use crate::command::value::Value;
+
+pub const BUILTINS: &[Builtin] = &[
+    Builtin { name: "print", function: print },
+    Builtin { name: "keys", function: keys },
+    Builtin { name: "len", function: len },
+];
+
+/// Writes each argument to standard output. Strings are written without quotes, and streams are copied as they arrive.
+fn print(args: Arguments) -> BuiltinResult {
+    Box::pin(async move {
+        let mut stdout = std::io::stdout();
+
+        for arg in args.positional {
+            match arg {
+                Value::String(str) => writeln!(stdout, "{}", str),
+                Value::Bytes(bytes) => stdout.write_all(&bytes),
+                Value::Stream(mut stream) => {
+                    while let Some(chunk) = stream.next().await {
+                        stdout.write_all(&chunk).map_err(RuntimeError::output)?;
+                    }
+                    Ok(())
+                }
+                value => writeln!(stdout, "{:#}", value),
+            }.map_err(RuntimeError::output)?;
+        }
+
+        stdout.flush().map_err(RuntimeError::output)?;
+
+        Ok(Value::Null)
+    })
+}
+
+/// Lists the keys of a dict, or the indices of a list.
+fn keys(args: Arguments) -> BuiltinResult {
+    Box::pin(async move {
+        match args.require("keys", "value", 0)? {
+            Value::Dict(dict) => Ok(Value::List(dict.keys().cloned().map(Value::String).collect())),
+            Value::List(list) => Ok(Value::List((0..list.len() as i64).map(Value::Integer).collect())),
+            value => Err(RuntimeError::InvalidArgument("keys".to_owned(), format!("expected dict or list, got {}", value.type_name())))
+        }
+    })
+}
+
+fn len(args: Arguments) -> BuiltinResult {
+    Box::pin(async move {
+        match args.require("len", "value", 0)? {
+            Value::Dict(dict) => Ok(Value::Integer(dict.len() as i64)),
+            Value::List(list) => Ok(Value::Integer(list.len() as i64)),
+            Value::String(str) => Ok(Value::Integer(str.chars().count() as i64)),
+            Value::Bytes(bytes) => Ok(Value::Integer(bytes.len() as i64)),
+            value => Err(RuntimeError::InvalidArgument("len".to_owned(), format!("expected a collection, got {}", value.type_name())))
+        }
+    })
+}
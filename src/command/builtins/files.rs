@@ -0,0 +1,481 @@
+use std::collections::HashSet;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use crate::command::builtins::{formats, Arguments, Builtin, BuiltinResult};
+use crate::command::eval::check_exit;
+use crate::command::fs::{self, Metadata};
+use crate::command::location::Location;
+use crate::command::runtime_err::RuntimeError;
+use crate::command::value::{Dict, Value};
+
+pub const BUILTINS: &[Builtin] = &[
+    Builtin { name: "readdir", function: readdir },
+    Builtin { name: "read_file", function: read_file },
+    Builtin { name: "write_file", function: write_file },
+    Builtin { name: "append_file", function: append_file },
+    Builtin { name: "locate_file", function: locate_file },
+    Builtin { name: "stat", function: stat },
+    Builtin { name: "mkdir", function: mkdir },
+    Builtin { name: "copy", function: copy },
+    Builtin { name: "move", function: move_file },
+    Builtin { name: "remove", function: remove },
+];
+
+/// The location given for an argument, as a location value or the text of one
+fn location(function: &str, args: &Arguments, name: &str, position: usize) -> Result<Location, RuntimeError> {
+    let value = args.require(function, name, position)?;
+
+    Location::from_value(value)
+        .ok_or_else(|| RuntimeError::InvalidArgument(function.to_owned(), format!("expected location, got {}", value.type_name())))
+}
+
+/// An optional argument given as text, such as a format
+fn option<'a>(function: &str, args: &'a Arguments, name: &str, position: usize) -> Result<Option<&'a str>, RuntimeError> {
+    match args.get(name, position) {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::String(option)) => Ok(Some(option)),
+        Some(value) => Err(RuntimeError::InvalidArgument(function.to_owned(), format!("expected '{}' to be a string, got {}", name, value.type_name()))),
+    }
+}
+
+/// The record of a location as it is now
+fn describe(location: &Location) -> Result<Value, RuntimeError> {
+    fs::with(location, |fs, path| fs.metadata(path, false)).map(|metadata| record(location, &metadata))
+}
+
+/// Describes a file or directory as a dict, with the fields `readdir` lists each entry with.
+pub fn record(location: &Location, metadata: &Metadata) -> Value {
+    let time = |time: Option<SystemTime>| time.map_or(Value::Null, |time| Value::Date(time.into()));
+    let name = |name: &Option<String>| name.clone().map_or(Value::Null, Value::String);
+
+    Value::Dict(Dict::from_iter([
+        ("directory", Value::Boolean(metadata.directory)),
+        ("file", Value::Boolean(metadata.file)),
+        ("symlink", Value::Boolean(metadata.symlink)),
+        ("mtime", time(metadata.modified)),
+        ("ctime", time(metadata.changed)),
+        ("atime", time(metadata.accessed)),
+        ("size", Value::Integer(metadata.size as i64)),
+        ("owner", name(&metadata.owner)),
+        ("group", name(&metadata.group)),
+        ("permissions", Value::Integer(metadata.permissions as i64)),
+        ("executable", Value::Boolean(metadata.executable)),
+        ("readable", Value::Boolean(metadata.readable)),
+        ("writable", Value::Boolean(metadata.writable)),
+        ("hidden", Value::Boolean(location.name().starts_with('.'))),
+        ("path", Value::Location(location.clone())),
+        ("name", Value::String(location.name().to_owned())),
+    ].map(|(key, value)| (key.to_owned(), value))))
+}
+
+struct Listing {
+    recursive: bool,
+    follow: bool,
+    hidden: bool,
+}
+
+/// Lists a directory, or the current directory if none is given, as a dict of records by name. Entries whose names
+/// start with a dot are left out unless `hidden` is set. With `recursive`, the contents of subdirectories are listed
+/// too, keyed by their path from the directory. Links are described as they are, unless `follow` is set, in which case
+/// they're described by what they link to and are recursed into.
+fn readdir(args: Arguments) -> BuiltinResult {
+    Box::pin(async move {
+        let dir = match args.get("file", 0) {
+            Some(_) => location("readdir", &args, "file", 0)?,
+            None => Location::local("."),
+        };
+
+        let listing = Listing {
+            recursive: args.flag("readdir", "recursive")?,
+            follow: args.flag("readdir", "follow")?,
+            hidden: args.flag("readdir", "hidden")?,
+        };
+
+        let mut visited = HashSet::from_iter(fs::with(&dir, |fs, path| fs.metadata(path, true))?.id);
+        let mut entries = Dict::new();
+        list(&dir, "", &listing, &mut visited, &mut entries)?;
+
+        Ok(Value::Dict(entries))
+    })
+}
+
+/// Adds the entries of `dir` to `entries`, with their names after `prefix`. Each directory is only listed once, so that
+/// following a link back up the tree doesn't go on forever.
+fn list(dir: &Location, prefix: &str, listing: &Listing, visited: &mut HashSet<(u64, u64)>, entries: &mut Dict) -> Result<(), RuntimeError> {
+    let mut names = fs::with(dir, |fs, path| fs.read_dir(path))?;
+    names.sort();
+
+    for name in names.into_iter().filter(|name| listing.hidden || !name.starts_with('.')) {
+        let location = dir.join(&name);
+        let metadata = fs::with(&location, |fs, path| fs.metadata(path, listing.follow))?;
+        let key = format!("{}{}", prefix, name);
+
+        // Directories which can't be read are listed, but not what's in them
+        let descend = listing.recursive && metadata.directory && metadata.readable && metadata.id.is_none_or(|id| visited.insert(id));
+
+        entries.insert(key.clone(), record(&location, &metadata));
+
+        if descend {
+            list(&location, &format!("{}/", key), listing, visited, entries)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads a file. It's read as text if it's valid UTF-8 and as bytes otherwise, unless `format` asks for `'text'`,
+/// `'bytes'` or one of the data formats, such as `'json'` or `'csv'`, which is parsed as its builtin does. A `'stream'`
+/// reads the file a chunk at a time as it's consumed, so large files needn't fit in memory.
+fn read_file(args: Arguments) -> BuiltinResult {
+    Box::pin(async move {
+        let file = location("read_file", &args, "file", 0)?;
+
+        match option("read_file", &args, "format", 1)? {
+            Some("stream") => fs::with(&file, |fs, path| fs.open(path)).map(Value::Stream),
+            format => decode(&file, fs::with(&file, |fs, path| fs.read(path))?, format).await,
+        }
+    })
+}
+
+/// Interprets the contents of a file in the given format
+async fn decode(file: &Location, contents: Vec<u8>, format: Option<&str>) -> Result<Value, RuntimeError> {
+    match format {
+        None => Ok(String::from_utf8(contents).map_or_else(|err| Value::Bytes(err.into_bytes()), Value::String)),
+        Some("text") => String::from_utf8(contents)
+            .map(Value::String)
+            .map_err(|_| RuntimeError::FileError(file.to_string(), ErrorKind::InvalidData, "the file isn't valid UTF-8, so can only be read as bytes".to_owned())),
+        Some("bytes") => Ok(Value::Bytes(contents)),
+        Some(format) => formats::parse("read_file", format, Value::Bytes(contents)).await,
+    }
+}
+
+/// Converts a value to the contents of a file in the given format. As text, strings and bytes are written as they are,
+/// streams as they're read and anything else in its printed form. Data formats are written as their `to_` builtins do.
+async fn encode(function: &str, value: Value, format: Option<&str>) -> Result<Vec<u8>, RuntimeError> {
+    match format {
+        None | Some("text") => {
+            let stream = value.into_stream();
+            let contents = stream.clone().merge().await;
+            check_exit(&stream).await?;
+            Ok(contents)
+        }
+        Some(format) => formats::serialise(function, format, value).await.map(String::into_bytes),
+    }
+}
+
+/// Writes a value to a file, replacing what was in it, and describes the file. The value comes first so that it can
+/// be piped in, as in `data | write_file('out.txt')`.
+fn write_file(args: Arguments) -> BuiltinResult {
+    Box::pin(write("write_file", args, false))
+}
+
+/// Writes a value to the end of a file, as `write_file` does
+fn append_file(args: Arguments) -> BuiltinResult {
+    Box::pin(write("append_file", args, true))
+}
+
+async fn write(function: &str, args: Arguments, append: bool) -> Result<Value, RuntimeError> {
+    let value = args.require(function, "value", 0)?.clone();
+    let file = location(function, &args, "file", 1)?;
+    let contents = encode(function, value, option(function, &args, "format", 2)?).await?;
+
+    fs::with(&file, |fs, path| fs.write(path, &contents, append))?;
+    describe(&file)
+}
+
+/// The XDG base directories for config and data, with the user's before the system's
+fn xdg_dirs() -> Vec<PathBuf> {
+    let list = |var: &str, default: &str| std::env::var(var).ok()
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| default.to_owned())
+        .split(':')
+        .map(PathBuf::from)
+        .collect::<Vec<_>>();
+
+    dirs::config_dir().into_iter()
+        .chain(list("XDG_CONFIG_DIRS", "/etc/xdg"))
+        .chain(dirs::data_dir())
+        .chain(list("XDG_DATA_DIRS", "/usr/local/share:/usr/share"))
+        .collect()
+}
+
+/// Looks for a file by name in the directory `from`, or the current directory, and in each directory above it, and then
+/// in the XDG config and data directories. Every match is described, nearest first.
+fn locate_file(args: Arguments) -> BuiltinResult {
+    Box::pin(async move {
+        let name = match args.require("locate_file", "name", 0)? {
+            Value::String(name) => name,
+            value => return Err(RuntimeError::InvalidArgument("locate_file".to_owned(), format!("expected string, got {}", value.type_name()))),
+        };
+
+        let from = match args.get("from", 1) {
+            Some(_) => location("locate_file", &args, "from", 1)?,
+            None => Location::local("."),
+        };
+
+        let dirs = std::iter::successors(Some(from), Location::parent).chain(xdg_dirs().into_iter().map(Location::local));
+        let mut seen = HashSet::new();
+        let mut found = Vec::new();
+
+        for candidate in dirs.map(|dir| dir.join(name)) {
+            if seen.insert(candidate.clone()) {
+                if let Ok(metadata) = fs::with(&candidate, |fs, path| fs.metadata(path, false)) {
+                    found.push(record(&candidate, &metadata));
+                }
+            }
+        }
+
+        Ok(Value::List(found))
+    })
+}
+
+/// Describes a file or directory with the same fields as `readdir`. Links are described as they are unless `follow` is
+/// set.
+fn stat(args: Arguments) -> BuiltinResult {
+    Box::pin(async move {
+        let file = location("stat", &args, "file", 0)?;
+        let follow = args.flag("stat", "follow")?;
+
+        fs::with(&file, |fs, path| fs.metadata(path, follow)).map(|metadata| record(&file, &metadata))
+    })
+}
+
+/// Creates a directory and describes it. With `parents`, any missing directories above it are created too, and it's
+/// fine for the directory to exist already.
+fn mkdir(args: Arguments) -> BuiltinResult {
+    Box::pin(async move {
+        let dir = location("mkdir", &args, "dir", 0)?;
+
+        if !args.flag("mkdir", "parents")? {
+            fs::with(&dir, |fs, path| fs.create_dir(path))?;
+            return describe(&dir);
+        }
+
+        let missing: Vec<Location> = std::iter::successors(Some(dir.clone()), Location::parent)
+            .take_while(|dir| fs::with(dir, |fs, path| fs.metadata(path, true)).is_err())
+            .collect();
+
+        for dir in missing.iter().rev() {
+            fs::with(dir, |fs, path| fs.create_dir(path))?;
+        }
+
+        match fs::with(&dir, |fs, path| fs.metadata(path, true))? {
+            metadata if metadata.directory => Ok(record(&dir, &metadata)),
+            _ => Err(RuntimeError::FileError(dir.to_string(), ErrorKind::AlreadyExists, "a file is already there".to_owned())),
+        }
+    })
+}
+
+/// Whether `location` is `dir` or somewhere inside it
+fn is_within(location: &Location, dir: &Location) -> bool {
+    location.scheme() == dir.scheme() && (location == dir || location.path().starts_with(&format!("{}/", dir.path().trim_end_matches('/'))))
+}
+
+/// Where copying or moving `from` to `to` puts it. As with `cp` and `mv`, a directory which already exists receives it
+/// under its own name.
+fn destination(from: &Location, to: Location) -> Location {
+    match fs::with(&to, |fs, path| fs.metadata(path, true)) {
+        Ok(metadata) if metadata.directory => to.join(from.name()),
+        _ => to,
+    }
+}
+
+fn copy_tree(from: &Location, to: &Location, recursive: bool) -> Result<(), RuntimeError> {
+    if !fs::with(from, |fs, path| fs.metadata(path, true))?.directory {
+        let contents = fs::with(from, |fs, path| fs.read(path))?;
+        return fs::with(to, |fs, path| fs.write(path, &contents, false));
+    } else if !recursive {
+        return Err(RuntimeError::FileError(from.to_string(), ErrorKind::IsADirectory, "directories are only copied with 'recursive: true'".to_owned()));
+    }
+
+    // Copying onto a directory which already exists merges the two
+    if !fs::with(to, |fs, path| fs.metadata(path, true)).is_ok_and(|metadata| metadata.directory) {
+        fs::with(to, |fs, path| fs.create_dir(path))?;
+    }
+
+    for name in fs::with(from, |fs, path| fs.read_dir(path))? {
+        copy_tree(&from.join(&name), &to.join(&name), true)?;
+    }
+
+    Ok(())
+}
+
+fn remove_tree(location: &Location, recursive: bool) -> Result<(), RuntimeError> {
+    if recursive && fs::with(location, |fs, path| fs.metadata(path, false))?.directory {
+        for name in fs::with(location, |fs, path| fs.read_dir(path))? {
+            remove_tree(&location.join(&name), true)?;
+        }
+    }
+
+    fs::with(location, |fs, path| fs.remove(path))
+}
+
+/// Copies a file, or a directory and everything in it if `recursive` is set, and describes the copy. Copies may go
+/// between schemes, as from `file:` to `mem:`.
+fn copy(args: Arguments) -> BuiltinResult {
+    Box::pin(async move {
+        let from = location("copy", &args, "from", 0)?;
+        let to = destination(&from, location("copy", &args, "to", 1)?);
+
+        if is_within(&to, &from) {
+            return Err(RuntimeError::InvalidArgument("copy".to_owned(), format!("cannot copy {} into itself", from)));
+        }
+
+        copy_tree(&from, &to, args.flag("copy", "recursive")?)?;
+        describe(&to)
+    })
+}
+
+/// Moves a file or directory and describes it in its new place. Within a scheme it's renamed, while between schemes
+/// it's copied across and the original removed.
+fn move_file(args: Arguments) -> BuiltinResult {
+    Box::pin(async move {
+        let from = location("move", &args, "from", 0)?;
+        let to = destination(&from, location("move", &args, "to", 1)?);
+
+        if from.scheme() == to.scheme() {
+            fs::with(&from, |fs, path| fs.rename(path, to.path()))?;
+        } else {
+            copy_tree(&from, &to, true)?;
+            remove_tree(&from, true)?;
+        }
+
+        describe(&to)
+    })
+}
+
+/// Removes a file, or a directory and everything in it if `recursive` is set, and describes what was removed.
+fn remove(args: Arguments) -> BuiltinResult {
+    Box::pin(async move {
+        let file = location("remove", &args, "file", 0)?;
+        let removed = describe(&file)?;
+
+        remove_tree(&file, args.flag("remove", "recursive")?)?;
+        Ok(removed)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::ErrorKind;
+
+    use crate::command::eval::run;
+    use crate::command::fs;
+    use crate::command::location::Location;
+    use crate::command::runtime_err::RuntimeError;
+    use crate::command::scope::Environment;
+    use crate::command::value::Value;
+
+    #[tokio::test]
+    pub async fn test_readdir() -> Result<(), RuntimeError> {
+        let env = Environment::new();
+        let root = Location::parse("mem:/test-readdir");
+
+        fs::with(&root, |fs, path| fs.create_dir(path))?;
+        fs::with(&root.join("config"), |fs, path| fs.create_dir(path))?;
+        fs::with(&root.join("config/esh.toml"), |fs, path| fs.write(path, b"prompt = '> '\n", false))?;
+        fs::with(&root.join(".profile"), |fs, path| fs.write(path, b"", false))?;
+
+        assert_eq!(run(&env, "readdir(file: 'mem:/test-readdir') | keys").await?.to_string(), "{ 'config' }");
+        assert_eq!(run(&env, "readdir('mem:/test-readdir', hidden: true, recursive: true) | keys").await?.to_string(), "{ '.profile', 'config', 'config/esh.toml' }");
+
+        run(&env, "entry = readdir(file: 'mem:/test-readdir/config').'esh.toml'").await?;
+        assert_eq!(run(&env, "entry | keys").await?.to_string(),
+            "{ 'directory', 'file', 'symlink', 'mtime', 'ctime', 'atime', 'size', 'owner', 'group', 'permissions', 'executable', 'readable', 'writable', 'hidden', 'path', 'name' }");
+        assert!(matches!(run(&env, "entry.mtime").await?, Value::Date(_)));
+        assert_eq!(run(&env, "entry.size").await?.to_string(), "14");
+        assert_eq!(run(&env, "entry.permissions").await?.to_string(), "420");
+        assert_eq!(run(&env, "entry.path").await?.to_string(), "Location('mem:/test-readdir/config/esh.toml')");
+
+        assert!(matches!(run(&env, "readdir(file: 'mem:/test-readdir/missing')").await, Err(RuntimeError::FileError(_, ErrorKind::NotFound, _))));
+        assert!(matches!(run(&env, "readdir(file: 'mem:/test-readdir', recursive: 1)").await, Err(RuntimeError::InvalidArgument(..))));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    pub async fn test_readdir_follows_links() -> Result<(), RuntimeError> {
+        let dir = std::env::temp_dir().join(format!("esh-test-readdir-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        std::fs::write(dir.join("sub/file"), "").unwrap();
+        std::os::unix::fs::symlink(&dir, dir.join("sub/up")).unwrap();
+
+        let env = Environment::new();
+        env.define("dir", Value::Location(Location::local(&dir)));
+
+        assert_eq!(run(&env, "readdir(dir, recursive: true) | keys").await?.to_string(), "{ 'sub', 'sub/file', 'sub/up' }");
+        assert_eq!(run(&env, "readdir(dir, recursive: true).'sub/up'.symlink").await?.to_string(), "true");
+        assert_eq!(run(&env, "readdir(dir, recursive: true).'sub/up'.directory").await?.to_string(), "false");
+
+        // The link leads back to where the listing started, so it isn't listed again
+        assert_eq!(run(&env, "readdir(dir, recursive: true, follow: true) | keys").await?.to_string(), "{ 'sub', 'sub/file', 'sub/up' }");
+        assert_eq!(run(&env, "readdir(dir, follow: true).sub.owner").await?.to_string(), run(&env, "readdir(dir).sub.owner").await?.to_string());
+
+        std::fs::remove_dir_all(dir).unwrap();
+        Ok(())
+    }
+
+    #[tokio::test]
+    pub async fn test_file_io() -> Result<(), RuntimeError> {
+        let env = Environment::new();
+
+        assert_eq!(run(&env, "mkdir('mem:/test-files/notes/old', parents: true).directory").await?.to_string(), "true");
+        assert_eq!(run(&env, "mkdir('mem:/test-files/notes', parents: true).name").await?.to_string(), "'notes'");
+        assert_eq!(run(&env, "'milk' | write_file('mem:/test-files/notes/todo.txt') | .size").await?.to_string(), "4");
+        assert_eq!(run(&env, "append_file(', eggs', 'mem:/test-files/notes/todo.txt').size").await?.to_string(), "10");
+        assert_eq!(run(&env, "read_file('mem:/test-files/notes/todo.txt')").await?.to_string(), "'milk, eggs'");
+        assert_eq!(run(&env, "read_file('mem:/test-files/notes/todo.txt', format: 'bytes')").await?.to_string(), run(&env, "b'milk, eggs'").await?.to_string());
+        assert_eq!(run(&env, "write_file({ a: { 1, 2.5 } }, 'mem:/test-files/data.json', format: 'json').size").await?.to_string(), "32");
+        assert_eq!(run(&env, "read_file('mem:/test-files/data.json', format: 'json')").await?.to_string(), "{ a: { 1, 2.5 } }");
+
+        run(&env, "write_file(b'\\xff', 'mem:/test-files/binary')").await?;
+        assert!(matches!(run(&env, "read_file('mem:/test-files/binary')").await?, Value::Bytes(bytes) if bytes == [0xff]));
+        assert_eq!(run(&env, "try { read_file('mem:/test-files/binary', format: 'text') } catch err { err.reason }").await?.to_string(), "'invalid_data'");
+
+        assert_eq!(run(&env, "copy('mem:/test-files/notes', 'mem:/test-files/archive', recursive: true).path").await?.to_string(), "Location('mem:/test-files/archive')");
+        assert_eq!(run(&env, "readdir('mem:/test-files/archive', recursive: true) | keys").await?.to_string(), "{ 'old', 'todo.txt' }");
+        assert_eq!(run(&env, "copy('mem:/test-files/notes/todo.txt', 'mem:/test-files/archive/old').path").await?.to_string(), "Location('mem:/test-files/archive/old/todo.txt')");
+        assert_eq!(run(&env, "move('mem:/test-files/archive', 'mem:/test-files/backup').name").await?.to_string(), "'backup'");
+        assert_eq!(run(&env, "read_file('mem:/test-files/backup/old/todo.txt')").await?.to_string(), "'milk, eggs'");
+        assert_eq!(run(&env, "remove('mem:/test-files/backup', recursive: true).directory").await?.to_string(), "true");
+
+        assert_eq!(run(&env, "try { read_file('mem:/test-files/backup/todo.txt') } catch err { err.reason }").await?.to_string(), "'not_found'");
+        assert_eq!(run(&env, "try { remove('mem:/test-files/notes') } catch err { err.reason }").await?.to_string(), "'directory_not_empty'");
+        assert_eq!(run(&env, "try { copy('mem:/test-files/notes', 'mem:/test-files/copy') } catch err { err.reason }").await?.to_string(), "'is_a_directory'");
+        assert_eq!(run(&env, "try { mkdir('mem:/test-files/notes') } catch err { err.kind + ' ' + err.path }").await?.to_string(), "'IOError mem:/test-files/notes'");
+        assert!(matches!(run(&env, "copy('mem:/test-files/notes', 'mem:/test-files/notes/old', recursive: true)").await, Err(RuntimeError::InvalidArgument(..))));
+        assert!(matches!(run(&env, "read_file('mem:/test-files/notes/todo.txt', format: 'pdf')").await, Err(RuntimeError::InvalidArgument(..))));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    pub async fn test_local_files() -> Result<(), RuntimeError> {
+        let dir = std::env::temp_dir().join(format!("esh-test-files-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("project/src")).unwrap();
+        std::fs::write(dir.join("esh.toml"), "").unwrap();
+
+        let env = Environment::new();
+        env.define("dir", Value::String(dir.to_string_lossy().into_owned()));
+
+        run(&env, "write_file('a line\\n', dir + '/project/lines')").await?;
+        match run(&env, "read_file(dir + '/project/lines', format: 'stream')").await? {
+            Value::Stream(stream) => assert_eq!(stream.merge().await, b"a line\n"),
+            value => panic!("expected a stream, got {}", value),
+        }
+
+        // The nearest match comes first, and the search carries on past it
+        assert_eq!(run(&env, "locate_file('esh.toml', from: dir + '/project/src').0.path").await?.to_string(), format!("Location('file:{}/esh.toml')", dir.display()));
+        assert_eq!(run(&env, "read_file(locate_file('esh.toml', from: dir + '/project/src') | .0.path)").await?.to_string(), "''");
+        assert_eq!(run(&env, "stat(dir + '/esh.toml').file").await?.to_string(), "true");
+
+        run(&env, "move(dir + '/project/lines', 'mem:/test-local-lines')").await?;
+        assert!(!dir.join("project/lines").exists());
+        assert_eq!(run(&env, "read_file('mem:/test-local-lines')").await?.to_string(), "'a line\n'");
+
+        std::fs::remove_dir_all(dir).unwrap();
+        Ok(())
+    }
+}
@@ -0,0 +1,429 @@
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
+use futures::StreamExt;
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::command::builtins::{tables, Arguments, Builtin, BuiltinResult};
+use crate::command::eval::{check_exit, invoke};
+use crate::command::runtime_err::RuntimeError;
+use crate::command::stream::ByteStream;
+use crate::command::value::Value;
+
+pub const BUILTINS: &[Builtin] = &[
+    Builtin { name: "json", function: json },
+    Builtin { name: "to_json", function: to_json },
+    Builtin { name: "ndjson", function: ndjson },
+    Builtin { name: "to_ndjson", function: to_ndjson },
+    Builtin { name: "toml", function: toml },
+    Builtin { name: "to_toml", function: to_toml },
+    Builtin { name: "yaml", function: yaml },
+    Builtin { name: "to_yaml", function: to_yaml },
+];
+
+lazy_static! {
+    static ref POSITION: Regex = Regex::new(r" at line \d+ column \d+").unwrap();
+}
+
+/// Reads a stream to the end as text, or as bytes if it isn't valid UTF-8. The stream fails if the process behind it
+/// did. Other values are left as they are.
+pub async fn resolve(value: Value) -> Result<Value, RuntimeError> {
+    let Value::Stream(stream) = value else {
+        return Ok(value);
+    };
+
+    let contents = stream.clone().merge().await;
+    check_exit(&stream).await?;
+
+    Ok(String::from_utf8(contents).map_or_else(|err| Value::Bytes(err.into_bytes()), Value::String))
+}
+
+/// The input to a parser, which may be text, bytes or the output of a process
+pub async fn input(function: &str, value: Value) -> Result<Vec<u8>, RuntimeError> {
+    match resolve(value).await? {
+        Value::String(str) => Ok(str.into_bytes()),
+        Value::Bytes(bytes) => Ok(bytes),
+        value => Err(RuntimeError::InvalidArgument(function.to_owned(), format!("expected string, bytes or stream, got {}", value.type_name()))),
+    }
+}
+
+/// The input to a parser which reads it a piece at a time. Streams are read a chunk at a time as they're needed, so
+/// they never have to be held in memory all at once.
+pub enum Chunks {
+    Bytes(Option<Vec<u8>>),
+    Stream(ByteStream),
+}
+
+impl Chunks {
+    pub fn new(function: &str, value: Value) -> Result<Chunks, RuntimeError> {
+        match value {
+            Value::String(str) => Ok(Chunks::Bytes(Some(str.into_bytes()))),
+            Value::Bytes(bytes) => Ok(Chunks::Bytes(Some(bytes))),
+            Value::Stream(stream) => Ok(Chunks::Stream(stream)),
+            value => Err(RuntimeError::InvalidArgument(function.to_owned(), format!("expected string, bytes or stream, got {}", value.type_name()))),
+        }
+    }
+
+    pub async fn next(&mut self) -> Option<Vec<u8>> {
+        match self {
+            Chunks::Bytes(bytes) => bytes.take(),
+            Chunks::Stream(stream) => stream.next().await,
+        }
+    }
+
+    /// Fails if the input came from a process which failed. It's checked once the input has been read.
+    pub async fn finish(&self) -> Result<(), RuntimeError> {
+        match self {
+            Chunks::Bytes(_) => Ok(()),
+            Chunks::Stream(stream) => check_exit(stream).await,
+        }
+    }
+}
+
+/// Where the records a parser reads go. They're collected into a list, unless an `each` function is given, in which
+/// case each is passed to it as soon as it's read and then dropped.
+#[derive(Default)]
+pub struct Records {
+    each: Option<Value>,
+    list: Vec<Value>,
+}
+
+impl Records {
+    pub fn new(function: &str, args: &Arguments) -> Result<Records, RuntimeError> {
+        let each = match args.named.get("each") {
+            None | Some(Value::Null) => None,
+            Some(each @ (Value::Function(_) | Value::Location(_))) => Some(each.clone()),
+            Some(value) => return Err(RuntimeError::InvalidArgument(function.to_owned(), format!("expected 'each' to be a function, got {}", value.type_name()))),
+        };
+
+        Ok(Records { each, list: vec![] })
+    }
+
+    pub async fn push(&mut self, record: Value) -> Result<(), RuntimeError> {
+        match &self.each {
+            Some(each) => {
+                invoke(each.clone(), Arguments { positional: vec![record], ..Default::default() }).await?;
+            }
+            None => self.list.push(record),
+        }
+
+        Ok(())
+    }
+
+    /// The list of records, or null if they were passed to `each`
+    pub fn finish(self) -> Value {
+        match self.each {
+            Some(_) => Value::Null,
+            None => Value::List(self.list),
+        }
+    }
+}
+
+/// The byte offset of a line and column, both counted from one, as parsers report them
+pub fn offset(text: &[u8], line: usize, column: usize) -> usize {
+    let start: usize = text.split_inclusive(|byte| *byte == b'\n').take(line.saturating_sub(1)).map(<[u8]>::len).sum();
+    (start + column.saturating_sub(1)).min(text.len())
+}
+
+/// Fails with the offset of the first invalid byte unless the input is UTF-8
+fn utf8<'a>(format: &str, text: &'a [u8]) -> Result<&'a str, RuntimeError> {
+    std::str::from_utf8(text).map_err(|err| RuntimeError::ParseError(format.to_owned(), err.valid_up_to(), "the input isn't valid UTF-8".to_owned()))
+}
+
+/// Parses input in one of the data formats, as its builtin does with no options
+pub async fn parse(function: &str, format: &str, value: Value) -> Result<Value, RuntimeError> {
+    match format {
+        "json" => parse_json(&input(function, value).await?),
+        "ndjson" => parse_ndjson(Chunks::new(function, value)?, Records::default()).await,
+        "toml" => parse_toml(&input(function, value).await?),
+        "yaml" => parse_yaml(&input(function, value).await?),
+        "csv" => tables::parse_table(Chunks::new(function, value)?, b',', Default::default()).await,
+        "tsv" => tables::parse_table(Chunks::new(function, value)?, b'\t', Default::default()).await,
+        format => Err(RuntimeError::InvalidArgument(function.to_owned(), format!("unknown format '{}'", format))),
+    }
+}
+
+/// Writes a value in one of the data formats, as a file would hold it. JSON is indented, and ends with a newline as
+/// the others do.
+pub async fn serialise(function: &str, format: &str, value: Value) -> Result<String, RuntimeError> {
+    let value = resolve(value).await?;
+
+    match format {
+        "json" => write_json(function, &value, true).map(|json| json + "\n"),
+        "ndjson" => write_ndjson(function, &value),
+        "toml" => write_toml(function, &value),
+        "yaml" => write_yaml(function, &value),
+        "csv" => tables::write_table(function, &value, b','),
+        "tsv" => tables::write_table(function, &value, b'\t'),
+        format => Err(RuntimeError::InvalidArgument(function.to_owned(), format!("unknown format '{}'", format))),
+    }
+}
+
+pub fn parse_json(text: &[u8]) -> Result<Value, RuntimeError> {
+    serde_json::from_slice(text).map_err(|err| {
+        RuntimeError::ParseError("JSON".to_owned(), offset(text, err.line(), err.column()), POSITION.replace(&err.to_string(), "").into_owned())
+    })
+}
+
+pub fn write_json(function: &str, value: &Value, pretty: bool) -> Result<String, RuntimeError> {
+    match pretty {
+        true => serde_json::to_string_pretty(value),
+        false => serde_json::to_string(value),
+    }.map_err(|err| RuntimeError::InvalidArgument(function.to_owned(), err.to_string()))
+}
+
+/// Puts the keys of every dict within the value in order
+pub fn sort_keys(value: &mut Value) {
+    match value {
+        Value::Dict(dict) => {
+            dict.sort_keys();
+            dict.values_mut().for_each(sort_keys);
+        }
+        Value::List(list) => list.iter_mut().for_each(sort_keys),
+        _ => {}
+    }
+}
+
+/// Parses one JSON value per line, a line at a time. Blank lines are skipped, and errors give their offset in the whole
+/// input rather than the line.
+async fn parse_ndjson(mut chunks: Chunks, mut records: Records) -> Result<Value, RuntimeError> {
+    let mut buffer = vec![];
+    // The offset of the start of the buffer in the input
+    let mut consumed = 0;
+
+    loop {
+        let chunk = chunks.next().await;
+        let end_of_input = chunk.is_none();
+        buffer.extend(chunk.unwrap_or_default());
+
+        let mut start = 0;
+
+        while start < buffer.len() {
+            let end = match buffer[start..].iter().position(|byte| *byte == b'\n') {
+                Some(end) => start + end,
+                None if end_of_input => buffer.len(),
+                None => break,
+            };
+
+            let line = &buffer[start..end];
+
+            if !line.trim_ascii().is_empty() {
+                let record = parse_json(line).map_err(|err| match err {
+                    RuntimeError::ParseError(_, offset, message) => RuntimeError::ParseError("NDJSON".to_owned(), consumed + start + offset, message),
+                    err => err,
+                })?;
+
+                records.push(record).await?;
+            }
+
+            start = end + 1;
+        }
+
+        if end_of_input {
+            break;
+        }
+
+        buffer.drain(..start);
+        consumed += start;
+    }
+
+    chunks.finish().await?;
+    Ok(records.finish())
+}
+
+fn write_ndjson(function: &str, value: &Value) -> Result<String, RuntimeError> {
+    let Value::List(list) = value else {
+        return Err(RuntimeError::InvalidArgument(function.to_owned(), format!("expected list, got {}", value.type_name())));
+    };
+
+    list.iter().map(|value| write_json(function, value, false).map(|json| json + "\n")).collect()
+}
+
+pub fn parse_toml(text: &[u8]) -> Result<Value, RuntimeError> {
+    toml::from_str(utf8("TOML", text)?)
+        .map(|table| from_toml(toml::Value::Table(table)))
+        .map_err(|err| RuntimeError::ParseError("TOML".to_owned(), err.span().map_or(0, |span| span.start), err.message().to_owned()))
+}
+
+/// Converts TOML to values. Datetimes with a time become dates, in local time if they don't give an offset, while dates
+/// and times on their own are kept as text.
+fn from_toml(value: toml::Value) -> Value {
+    match value {
+        toml::Value::String(str) => Value::String(str),
+        toml::Value::Integer(int) => Value::Integer(int),
+        toml::Value::Float(float) => Value::Float(float),
+        toml::Value::Boolean(bool) => Value::Boolean(bool),
+        toml::Value::Datetime(datetime) => {
+            let text = datetime.to_string();
+
+            DateTime::parse_from_rfc3339(&text).map(|date| date.with_timezone(&Local)).ok()
+                .or_else(|| NaiveDateTime::parse_from_str(&text, "%Y-%m-%dT%H:%M:%S%.f").ok().and_then(|date| Local.from_local_datetime(&date).single()))
+                .map_or(Value::String(text), Value::Date)
+        }
+        toml::Value::Array(array) => Value::List(array.into_iter().map(from_toml).collect()),
+        toml::Value::Table(table) => Value::Dict(table.into_iter().map(|(key, value)| (key, from_toml(value))).collect()),
+    }
+}
+
+/// TOML documents are tables, so only dicts can be written. It has no null, so neither can anything containing one.
+fn write_toml(function: &str, value: &Value) -> Result<String, RuntimeError> {
+    match value {
+        Value::Dict(_) => toml::to_string(value).map_err(|err| RuntimeError::InvalidArgument(function.to_owned(), err.to_string())),
+        value => Err(RuntimeError::InvalidArgument(function.to_owned(), format!("expected dict, got {}", value.type_name()))),
+    }
+}
+
+/// Parses YAML straight into values, so that numbers are read just as they are from JSON
+pub fn parse_yaml(text: &[u8]) -> Result<Value, RuntimeError> {
+    serde_yaml::from_slice(text).map_err(|err| {
+        RuntimeError::ParseError("YAML".to_owned(), err.location().map_or(0, |location| location.index()), POSITION.replace(&err.to_string(), "").into_owned())
+    })
+}
+
+fn write_yaml(function: &str, value: &Value) -> Result<String, RuntimeError> {
+    serde_yaml::to_string(value).map_err(|err| RuntimeError::InvalidArgument(function.to_owned(), err.to_string()))
+}
+
+/// Parses JSON from text, bytes or the output of a process, as in `http(url) | json`. Numbers without a fraction or
+/// exponent become integers and the rest floats.
+fn json(args: Arguments) -> BuiltinResult {
+    Box::pin(async move {
+        let text = input("json", args.require("json", "text", 0)?.clone()).await?;
+        parse_json(&text)
+    })
+}
+
+/// Writes a value as JSON, compactly unless `pretty` is set. Dicts keep their keys in the order they were added, or
+/// sorted with `sort_keys`.
+fn to_json(args: Arguments) -> BuiltinResult {
+    Box::pin(async move {
+        let mut value = resolve(args.require("to_json", "value", 0)?.clone()).await?;
+
+        if args.flag("to_json", "sort_keys")? {
+            sort_keys(&mut value);
+        }
+
+        write_json("to_json", &value, args.flag("to_json", "pretty")?).map(Value::String)
+    })
+}
+
+/// Parses newline-delimited JSON into a list of the values on each line. Streams are read a line at a time, and with
+/// `each`, every value is passed to it as it's read instead of being kept.
+fn ndjson(args: Arguments) -> BuiltinResult {
+    Box::pin(async move {
+        let chunks = Chunks::new("ndjson", args.require("ndjson", "text", 0)?.clone())?;
+        parse_ndjson(chunks, Records::new("ndjson", &args)?).await
+    })
+}
+
+/// Writes a list as newline-delimited JSON, with each item on a line of its own
+fn to_ndjson(args: Arguments) -> BuiltinResult {
+    Box::pin(async move {
+        write_ndjson("to_ndjson", &resolve(args.require("to_ndjson", "list", 0)?.clone()).await?).map(Value::String)
+    })
+}
+
+fn toml(args: Arguments) -> BuiltinResult {
+    Box::pin(async move {
+        parse_toml(&input("toml", args.require("toml", "text", 0)?.clone()).await?)
+    })
+}
+
+fn to_toml(args: Arguments) -> BuiltinResult {
+    Box::pin(async move {
+        write_toml("to_toml", &resolve(args.require("to_toml", "value", 0)?.clone()).await?).map(Value::String)
+    })
+}
+
+/// Parses a YAML document. Documents holding more than one aren't supported.
+fn yaml(args: Arguments) -> BuiltinResult {
+    Box::pin(async move {
+        parse_yaml(&input("yaml", args.require("yaml", "text", 0)?.clone()).await?)
+    })
+}
+
+fn to_yaml(args: Arguments) -> BuiltinResult {
+    Box::pin(async move {
+        write_yaml("to_yaml", &resolve(args.require("to_yaml", "value", 0)?.clone()).await?).map(Value::String)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::command::eval::run;
+    use crate::command::runtime_err::RuntimeError;
+    use crate::command::scope::Environment;
+    use crate::command::value::Value;
+
+    #[tokio::test]
+    pub async fn test_json() -> Result<(), RuntimeError> {
+        let env = Environment::new();
+        env.define("text", Value::String(r#"{"users": [{"name": "Ada", "age": 36, "height": 1.65, "admin": true, "manager": null}], "total": 1.0}"#.to_owned()));
+
+        assert_eq!(run(&env, "text | json | .users.0").await?.to_string(), "{ name: 'Ada', age: 36, height: 1.65, admin: true, manager: null }");
+        assert!(matches!(run(&env, "(text | json).total").await?, Value::Float(total) if total == 1.0));
+        assert!(matches!(run(&env, "json('[1, 18446744073709551615]')").await, Err(RuntimeError::ParseError(_, 23, ref msg)) if msg == "integer 18446744073709551615 is too large"));
+        assert_eq!(run(&env, "json(b'[-9223372036854775808]')").await?.to_string(), "{ -9223372036854775808 }");
+        assert_eq!(run(&env, "sh('-c', 'echo \\'{\"a\": 2}\\'') | json | .a").await?.to_string(), "2");
+
+        // Values come back as they went in, with integers and floats kept apart
+        assert_eq!(run(&env, "text | json | to_json").await?.to_string(), format!("'{}'", r#"{"users":[{"name":"Ada","age":36,"height":1.65,"admin":true,"manager":null}],"total":1.0}"#));
+        assert_eq!(run(&env, "to_json({ b: 1, a: { d: {:}, c: { 1 } } }, sort_keys: true)").await?.to_string(), format!("'{}'", r#"{"a":{"c":[1],"d":{}},"b":1}"#));
+        assert!(matches!(run(&env, "to_json({ a: { 1 } }, pretty: true)").await?, Value::String(json) if json == "{\n  \"a\": [\n    1\n  ]\n}"));
+        assert_eq!(run(&env, "to_json(r'a+')").await?.to_string(), format!("'{}'", r#""a+""#));
+
+        assert_eq!(run(&env, "try { json('{\"a\": 1,\\n \"b\": }') } catch err { { err.kind, err.offset } }").await?.to_string(), "{ 'ParseError', 15 }");
+        assert_eq!(run(&env, "try { json('[1, x]') } catch err { err.message }").await?.to_string(), "'Invalid JSON at byte 4: expected value'");
+        assert_eq!(run(&env, "try { json('') } catch err { err.offset }").await?.to_string(), "0");
+        assert!(matches!(run(&env, "to_json(x -> x)").await, Err(RuntimeError::InvalidArgument(..))));
+        assert!(matches!(run(&env, "json(1)").await, Err(RuntimeError::InvalidArgument(..))));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    pub async fn test_ndjson() -> Result<(), RuntimeError> {
+        let env = Environment::new();
+        env.define("nothing", Value::Null);
+
+        assert_eq!(run(&env, "ndjson('{\"a\": 1}\\n\\n[2, 3.5]\\r\\n\"x\"')").await?.to_string(), "{ { a: 1 }, { 2, 3.5 }, 'x' }");
+        assert_eq!(run(&env, "to_ndjson({ { a: 1 }, nothing })").await?.to_string(), "'{\"a\":1}\nnull\n'");
+        assert_eq!(run(&env, "sh('-c', 'seq 1 3') | ndjson").await?.to_string(), "{ 1, 2, 3 }");
+
+        // Each value is handed over as it's read, rather than collected
+        run(&env, "total = 0").await?;
+        assert_eq!(run(&env, "sh('-c', 'seq 1 1000') | ndjson(each: n -> total = total + n)").await?.to_string(), "null");
+        assert_eq!(run(&env, "total").await?.to_string(), "500500");
+
+        assert_eq!(run(&env, "try { ndjson('1\\n2\\n[3,') } catch err { err.message }").await?.to_string(), "'Invalid NDJSON at byte 6: EOF while parsing a value'");
+        assert!(matches!(run(&env, "to_ndjson({ a: 1 })").await, Err(RuntimeError::InvalidArgument(..))));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    pub async fn test_toml_and_yaml() -> Result<(), RuntimeError> {
+        let env = Environment::new();
+        env.define("config", Value::String("prompt = '> '\nlogin_dir = 'file:{HOME}'\n\n[colours]\nerror = 1\nratio = 0.5\nupdated = 2024-03-01T12:30:00Z\nday = 2024-03-01\n".to_owned()));
+
+        assert_eq!(run(&env, "(config | toml).prompt").await?.to_string(), "'> '");
+        assert_eq!(run(&env, "config | toml | keys").await?.to_string(), "{ 'prompt', 'login_dir', 'colours' }");
+        assert!(matches!(run(&env, "(config | toml).colours.updated").await?, Value::Date(_)));
+        assert_eq!(run(&env, "(config | toml).colours.day").await?.to_string(), "'2024-03-01'");
+        assert_eq!(run(&env, "to_toml({ name: 'esh', deps: { regex: '1' } })").await?.to_string(), "'name = \"esh\"\n\n[deps]\nregex = \"1\"\n'");
+        assert_eq!(run(&env, "try { toml('a = ') } catch err { { err.kind, err.offset } }").await?.to_string(), "{ 'ParseError', 4 }");
+        assert!(matches!(run(&env, "to_toml({ 1 })").await, Err(RuntimeError::InvalidArgument(..))));
+        assert!(matches!(run(&env, "sh('-c', 'exit 4') | to_toml").await, Err(RuntimeError::ExitError(_, 4))));
+        assert!(matches!(run(&env, "sh('-c', 'echo a') | to_toml").await, Err(RuntimeError::InvalidArgument(_, ref msg)) if msg == "expected dict, got string"));
+
+        env.define("doc", Value::String("name: esh\nversion: 1\nratio: 0.5\ntags: [shell, rust]\n1: one\n".to_owned()));
+        assert_eq!(run(&env, "doc | yaml").await?.to_string(), "{ name: 'esh', version: 1, ratio: 0.5, tags: { 'shell', 'rust' }, '1': 'one' }");
+        assert_eq!(run(&env, "to_yaml({ name: 'esh', tags: { 'shell' } })").await?.to_string(), "'name: esh\ntags:\n- shell\n'");
+        assert_eq!(run(&env, "yaml('')").await?.to_string(), "null");
+        assert_eq!(run(&env, "try { yaml('a: [1,') } catch err { err.kind }").await?.to_string(), "'ParseError'");
+        assert_eq!(run(&env, "yaml('count: !int 3\nnested: { 2: two, true: yes }')").await?.to_string(), "{ count: 3, nested: { '2': 'two', true: 'yes' } }");
+        assert!(matches!(run(&env, "yaml('a: 18446744073709551615')").await, Err(RuntimeError::ParseError(_, 3, ref msg)) if msg == "a: integer 18446744073709551615 is too large"));
+        assert!(matches!(run(&env, "yaml('a: 1.8446744073709552e19')").await?, Value::Dict(ref dict) if matches!(dict["a"], Value::Float(_))));
+
+        Ok(())
+    }
+}
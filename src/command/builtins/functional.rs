@@ -0,0 +1,256 @@
+use std::cmp::Ordering;
+
+use crate::command::builtins::{Arguments, Builtin, BuiltinResult};
+use crate::command::eval::invoke;
+use crate::command::ops;
+use crate::command::runtime_err::RuntimeError;
+use crate::command::value::{Dict, Value};
+
+pub const BUILTINS: &[Builtin] = &[
+    Builtin { name: "map", function: map },
+    Builtin { name: "filter", function: filter },
+    Builtin { name: "reduce", function: reduce },
+    Builtin { name: "flat_map", function: flat_map },
+    Builtin { name: "sort_by", function: sort_by },
+    Builtin { name: "group_by", function: group_by },
+    Builtin { name: "any", function: any },
+    Builtin { name: "all", function: all },
+];
+
+fn list(function: &str, args: &Arguments) -> Result<Vec<Value>, RuntimeError> {
+    match args.require(function, "list", 0)? {
+        Value::List(list) => Ok(list.clone()),
+        value => Err(RuntimeError::InvalidArgument(function.to_owned(), format!("expected list, got {}", value.type_name())))
+    }
+}
+
+fn callback(function: &str, args: &Arguments) -> Result<Value, RuntimeError> {
+    match args.require(function, "function", 1)? {
+        value @ (Value::Function(_) | Value::Location(_)) => Ok(value.clone()),
+        value => Err(RuntimeError::InvalidArgument(function.to_owned(), format!("expected function, got {}", value.type_name())))
+    }
+}
+
+async fn apply(function: &Value, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    invoke(function.clone(), Arguments { positional: args, ..Default::default() }).await
+}
+
+/// Applies the function to every item of a list, or to every value of a dict keeping its keys.
+fn map(args: Arguments) -> BuiltinResult {
+    Box::pin(async move {
+        let function = callback("map", &args)?;
+
+        if let Some(Value::Dict(dict)) = args.get("list", 0) {
+            let mut mapped = Dict::new();
+
+            for (key, value) in dict {
+                mapped.insert(key.clone(), apply(&function, vec![value.clone()]).await?);
+            }
+
+            return Ok(Value::Dict(mapped));
+        }
+
+        let mut mapped = vec![];
+
+        for item in list("map", &args)? {
+            mapped.push(apply(&function, vec![item]).await?);
+        }
+
+        Ok(Value::List(mapped))
+    })
+}
+
+/// Keeps the items of a list, or the entries of a dict, for which the function returns a truthy value.
+fn filter(args: Arguments) -> BuiltinResult {
+    Box::pin(async move {
+        let function = callback("filter", &args)?;
+
+        if let Some(Value::Dict(dict)) = args.get("list", 0) {
+            let mut filtered = Dict::new();
+
+            for (key, value) in dict {
+                if apply(&function, vec![value.clone()]).await?.truthy() {
+                    filtered.insert(key.clone(), value.clone());
+                }
+            }
+
+            return Ok(Value::Dict(filtered));
+        }
+
+        let mut filtered = vec![];
+
+        for item in list("filter", &args)? {
+            if apply(&function, vec![item.clone()]).await?.truthy() {
+                filtered.push(item);
+            }
+        }
+
+        Ok(Value::List(filtered))
+    })
+}
+
+/// Folds a list into a single value with a function of the accumulator and each item. Without an initial value, the
+/// first item is used.
+fn reduce(args: Arguments) -> BuiltinResult {
+    Box::pin(async move {
+        let function = callback("reduce", &args)?;
+        let mut items = list("reduce", &args)?.into_iter();
+
+        let mut accumulator = match args.get("initial", 2) {
+            Some(initial) => initial.clone(),
+            None => items.next().ok_or_else(|| RuntimeError::InvalidArgument("reduce".to_owned(), "cannot reduce an empty list without an initial value".to_owned()))?
+        };
+
+        for item in items {
+            accumulator = apply(&function, vec![accumulator, item]).await?;
+        }
+
+        Ok(accumulator)
+    })
+}
+
+/// Maps each item to a list and concatenates the results. Results which aren't lists are kept as single items.
+fn flat_map(args: Arguments) -> BuiltinResult {
+    Box::pin(async move {
+        let function = callback("flat_map", &args)?;
+        let mut mapped = vec![];
+
+        for item in list("flat_map", &args)? {
+            match apply(&function, vec![item]).await? {
+                Value::List(list) => mapped.extend(list),
+                value => mapped.push(value),
+            }
+        }
+
+        Ok(Value::List(mapped))
+    })
+}
+
+/// Sorts a list by the key the function returns for each item. The sort is stable.
+fn sort_by(args: Arguments) -> BuiltinResult {
+    Box::pin(async move {
+        let function = callback("sort_by", &args)?;
+        let mut keyed = vec![];
+
+        for item in list("sort_by", &args)? {
+            keyed.push((apply(&function, vec![item.clone()]).await?, item));
+        }
+
+        let mut incomparable = None;
+
+        keyed.sort_by(|(a, _), (b, _)| ops::compare(a, b).unwrap_or_else(|| {
+            incomparable.get_or_insert_with(|| format!("cannot compare {} and {}", a.type_name(), b.type_name()));
+            Ordering::Equal
+        }));
+
+        match incomparable {
+            Some(err) => Err(RuntimeError::InvalidArgument("sort_by".to_owned(), err)),
+            None => Ok(Value::List(keyed.into_iter().map(|(_, item)| item).collect()))
+        }
+    })
+}
+
+/// Groups the items of a list into a dict of lists, keyed by the function's result for each item.
+fn group_by(args: Arguments) -> BuiltinResult {
+    Box::pin(async move {
+        let function = callback("group_by", &args)?;
+        let mut groups = Dict::new();
+
+        for item in list("group_by", &args)? {
+            let key = match apply(&function, vec![item.clone()]).await? {
+                Value::String(key) => key,
+                key => key.to_string(),
+            };
+
+            match groups.entry(key).or_insert_with(|| Value::List(vec![])) {
+                Value::List(group) => group.push(item),
+                _ => unreachable!(),
+            }
+        }
+
+        Ok(Value::Dict(groups))
+    })
+}
+
+/// Whether the function returns a truthy value for any item. Without a function, the items themselves are tested.
+fn any(args: Arguments) -> BuiltinResult {
+    Box::pin(async move {
+        let items = list("any", &args)?;
+
+        for item in items {
+            let result = match args.get("function", 1) {
+                Some(_) => apply(&callback("any", &args)?, vec![item]).await?,
+                None => item,
+            };
+
+            if result.truthy() {
+                return Ok(Value::Boolean(true));
+            }
+        }
+
+        Ok(Value::Boolean(false))
+    })
+}
+
+/// Whether the function returns a truthy value for every item. Without a function, the items themselves are tested.
+fn all(args: Arguments) -> BuiltinResult {
+    Box::pin(async move {
+        let items = list("all", &args)?;
+
+        for item in items {
+            let result = match args.get("function", 1) {
+                Some(_) => apply(&callback("all", &args)?, vec![item]).await?,
+                None => item,
+            };
+
+            if !result.truthy() {
+                return Ok(Value::Boolean(false));
+            }
+        }
+
+        Ok(Value::Boolean(true))
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::command::eval::run;
+    use crate::command::runtime_err::RuntimeError;
+    use crate::command::scope::Environment;
+    use crate::command::value::Value;
+
+    #[tokio::test]
+    pub async fn test_higher_order() -> Result<(), RuntimeError> {
+        let env = Environment::new();
+        env.define("numbers", Value::List((1..=5).map(Value::Integer).collect()));
+
+        assert_eq!(run(&env, "numbers | map(i -> i * 2)").await?.to_string(), "{ 2, 4, 6, 8, 10 }");
+        assert_eq!(run(&env, "numbers | filter(i -> i % 2 == 1)").await?.to_string(), "{ 1, 3, 5 }");
+        assert_eq!(run(&env, "numbers | reduce(acc; i -> acc + i)").await?.to_string(), "15");
+        assert_eq!(run(&env, "reduce(numbers, acc; i -> acc * i, 10)").await?.to_string(), "1200");
+        assert_eq!(run(&env, "numbers | flat_map(i -> numbers | filter(j -> j < i)) | len").await?.to_string(), "10");
+        assert_eq!(run(&env, "numbers | sort_by(i -> -i)").await?.to_string(), "{ 5, 4, 3, 2, 1 }");
+        assert_eq!(run(&env, "numbers | group_by(i -> i % 2 == 0)").await?.to_string(), "{ false: { 1, 3, 5 }, true: { 2, 4 } }");
+        assert_eq!(run(&env, "numbers | any(i -> i > 4)").await?.to_string(), "true");
+        assert_eq!(run(&env, "numbers | all(i -> i > 4)").await?.to_string(), "false");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    pub async fn test_closures_capture_scope() -> Result<(), RuntimeError> {
+        let env = Environment::new();
+        env.define("numbers", Value::List((1..=3).map(Value::Integer).collect()));
+
+        run(&env, "offset = 10").await?;
+        run(&env, "add_offset = i -> i + offset").await?;
+        run(&env, "offset = 20").await?;
+
+        assert_eq!(run(&env, "numbers | map(add_offset)").await?.to_string(), "{ 21, 22, 23 }");
+        assert_eq!(run(&env, "add_offset(1)").await?.to_string(), "21");
+        assert!(run(&env, "add_offset(1, 2)").await.is_err());
+        assert!(run(&env, "i").await.is_err());
+
+        Ok(())
+    }
+}
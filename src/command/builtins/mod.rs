@@ -0,0 +1,78 @@
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter};
+use std::future::Future;
+use std::pin::Pin;
+
+use lazy_static::lazy_static;
+
+use crate::command::runtime_err::RuntimeError;
+use crate::command::value::{Dict, Value};
+
+mod core;
+mod files;
+mod formats;
+mod functional;
+mod tables;
+
+/// The evaluated arguments of a call. When a builtin is used as a pipeline stage, the upstream value is inserted as the
+/// first positional argument.
+#[derive(Debug, Clone, Default)]
+pub struct Arguments {
+    pub positional: Vec<Value>,
+    pub named: Dict,
+}
+
+pub type BuiltinResult = Pin<Box<dyn Future<Output=Result<Value, RuntimeError>>>>;
+
+#[derive(Clone, Copy)]
+pub struct Builtin {
+    pub name: &'static str,
+    pub function: fn(Arguments) -> BuiltinResult,
+}
+
+impl Debug for Builtin {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Builtin({})", self.name)
+    }
+}
+
+impl Arguments {
+    /// Fetches an argument by name, falling back to its position.
+    pub fn get(&self, name: &str, position: usize) -> Option<&Value> {
+        self.named.get(name).or_else(|| self.positional.get(position))
+    }
+
+    pub fn require(&self, function: &str, name: &str, position: usize) -> Result<&Value, RuntimeError> {
+        self.get(name, position)
+            .ok_or_else(|| RuntimeError::InvalidArgument(function.to_owned(), format!("missing argument '{}'", name)))
+    }
+
+    /// An option which can only be given by name, and is off unless it's set to `true`
+    pub fn flag(&self, function: &str, name: &str) -> Result<bool, RuntimeError> {
+        match self.named.get(name) {
+            None => Ok(false),
+            Some(Value::Boolean(flag)) => Ok(*flag),
+            Some(value) => Err(RuntimeError::InvalidArgument(function.to_owned(), format!("expected '{}' to be a boolean, got {}", name, value.type_name()))),
+        }
+    }
+}
+
+lazy_static! {
+    static ref BUILTINS: HashMap<&'static str, Builtin> = {
+        let mut builtins = HashMap::new();
+
+        for builtin in core::BUILTINS.iter().chain(functional::BUILTINS).chain(files::BUILTINS).chain(formats::BUILTINS).chain(tables::BUILTINS) {
+            builtins.insert(builtin.name, *builtin);
+        }
+
+        builtins
+    };
+}
+
+pub fn lookup(name: &str) -> Option<Builtin> {
+    BUILTINS.get(name).copied()
+}
+
+pub fn names() -> impl Iterator<Item=&'static str> {
+    BUILTINS.keys().copied()
+}
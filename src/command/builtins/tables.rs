@@ -0,0 +1,412 @@
+use csv_core::{ReadRecordResult, Reader, ReaderBuilder};
+use indexmap::IndexSet;
+
+use crate::command::builtins::formats::{resolve, Chunks, Records};
+use crate::command::builtins::{Arguments, Builtin, BuiltinResult};
+use crate::command::runtime_err::RuntimeError;
+use crate::command::value::{Dict, Value};
+
+pub const BUILTINS: &[Builtin] = &[
+    Builtin { name: "csv", function: csv },
+    Builtin { name: "tsv", function: tsv },
+    Builtin { name: "to_csv", function: to_csv },
+    Builtin { name: "to_tsv", function: to_tsv },
+];
+
+/// How many records the types of a table's columns are inferred from. Only these are held back while the rest are
+/// read one at a time.
+const SAMPLE: usize = 1000;
+
+/// How a table is read
+#[derive(Default)]
+pub struct Options {
+    /// Whether the first record names the columns, or `None` to guess from what it holds
+    pub header: Option<bool>,
+    /// Keeps every field as text instead of inferring the types of the columns
+    pub raw: bool,
+    pub records: Records,
+}
+
+impl Options {
+    fn new(function: &str, args: &Arguments) -> Result<Options, RuntimeError> {
+        let header = match args.named.get("header") {
+            None | Some(Value::Null) => None,
+            Some(Value::Boolean(header)) => Some(*header),
+            Some(value) => return Err(RuntimeError::InvalidArgument(function.to_owned(), format!("expected 'header' to be a boolean, got {}", value.type_name()))),
+        };
+
+        Ok(Options { header, raw: args.flag(function, "raw")?, records: Records::new(function, args)? })
+    }
+}
+
+/// Reads the records of a table from its input, a chunk at a time
+struct TableReader {
+    format: &'static str,
+    chunks: Chunks,
+    reader: Reader,
+    chunk: Vec<u8>,
+    /// How far into the chunk has been read, and the offset of the chunk in the input
+    position: usize,
+    offset: usize,
+    end_of_input: bool,
+    output: Vec<u8>,
+    ends: Vec<usize>,
+}
+
+impl TableReader {
+    fn new(chunks: Chunks, delimiter: u8) -> TableReader {
+        TableReader {
+            format: if delimiter == b'\t' { "TSV" } else { "CSV" },
+            chunks,
+            reader: ReaderBuilder::new().delimiter(delimiter).build(),
+            chunk: vec![],
+            position: 0,
+            offset: 0,
+            end_of_input: false,
+            output: vec![0; 1024],
+            ends: vec![0; 16],
+        }
+    }
+
+    /// The fields of the next record, along with the offset it starts at
+    async fn next(&mut self) -> Result<Option<(usize, Vec<String>)>, RuntimeError> {
+        let start = self.offset + self.position;
+        let (mut output, mut ends) = (0, 0);
+
+        loop {
+            if self.position == self.chunk.len() && !self.end_of_input {
+                match self.chunks.next().await {
+                    Some(chunk) => {
+                        self.offset += self.chunk.len();
+                        self.chunk = chunk;
+                        self.position = 0;
+                    }
+                    None => self.end_of_input = true,
+                }
+            }
+
+            // An empty input tells the reader that there's no more to come
+            let (result, read, written, ended) = self.reader.read_record(&self.chunk[self.position..], &mut self.output[output..], &mut self.ends[ends..]);
+            self.position += read;
+            output += written;
+            ends += ended;
+
+            match result {
+                ReadRecordResult::InputEmpty => {}
+                ReadRecordResult::OutputFull => self.output.resize(self.output.len() * 2, 0),
+                ReadRecordResult::OutputEndsFull => self.ends.resize(self.ends.len() * 2, 0),
+                ReadRecordResult::Record => break,
+                ReadRecordResult::End => return Ok(None),
+            }
+        }
+
+        let fields = std::str::from_utf8(&self.output[..output])
+            .map_err(|_| RuntimeError::ParseError(self.format.to_owned(), start, "the record isn't valid UTF-8".to_owned()))?;
+
+        let mut field_start = 0;
+        let record = self.ends[..ends].iter()
+            .map(|&end| {
+                let field = fields[field_start..end].to_owned();
+                field_start = end;
+                field
+            })
+            .collect();
+
+        Ok(Some((start, record)))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Type {
+    Integer,
+    Float,
+    Boolean,
+    Text,
+}
+
+/// Numbers written with leading zeros, such as postcodes and IDs, are kept as text so that they aren't changed
+fn has_leading_zero(field: &str) -> bool {
+    let digits = field.trim_start_matches(['-', '+']);
+    digits.len() > 1 && digits.starts_with('0') && digits.as_bytes()[1].is_ascii_digit()
+}
+
+fn infer(field: &str) -> Type {
+    let numeric = field.bytes().any(|byte| byte.is_ascii_digit())
+        && field.bytes().all(|byte| byte.is_ascii_digit() || matches!(byte, b'-' | b'+' | b'.' | b'e' | b'E'))
+        && !has_leading_zero(field);
+
+    if numeric && field.parse::<i64>().is_ok() {
+        Type::Integer
+    } else if numeric && field.parse::<f64>().is_ok() {
+        Type::Float
+    } else if field.eq_ignore_ascii_case("true") || field.eq_ignore_ascii_case("false") {
+        Type::Boolean
+    } else {
+        Type::Text
+    }
+}
+
+/// The type of a column holding fields of both types
+fn widen(a: Type, b: Type) -> Type {
+    match (a, b) {
+        (a, b) if a == b => a,
+        (Type::Integer, Type::Float) | (Type::Float, Type::Integer) => Type::Float,
+        _ => Type::Text,
+    }
+}
+
+/// Converts a field to its column's type. Empty fields are null, and fields which don't fit the type, which can only
+/// come after the sample the types were inferred from, are kept as text.
+fn convert(field: String, column: Option<Type>) -> Value {
+    let value = match column {
+        _ if field.is_empty() => Some(Value::Null),
+        Some(Type::Integer) if infer(&field) == Type::Integer => field.parse().ok().map(Value::Integer),
+        Some(Type::Float) if matches!(infer(&field), Type::Integer | Type::Float) => field.parse().ok().map(Value::Float),
+        Some(Type::Boolean) if infer(&field) == Type::Boolean => Some(Value::Boolean(field.eq_ignore_ascii_case("true"))),
+        _ => None,
+    };
+
+    value.unwrap_or(Value::String(field))
+}
+
+/// Whether the first record of a table seems to name its columns, judged much as Python's `csv.Sniffer` does. Its fields
+/// have to be different pieces of text, and then each column votes on whether its first field stands out from the rest:
+/// it does if the rest are numbers or booleans, or if they're all one length and the first field isn't that length.
+fn is_header(first: &[String], rows: &[(usize, Vec<String>)]) -> bool {
+    if first.iter().any(|field| field.is_empty() || infer(field) != Type::Text) || first.iter().collect::<IndexSet<_>>().len() != first.len() {
+        return false;
+    }
+
+    let votes: i64 = first.iter().enumerate()
+        .map(|(column, name)| {
+            let fields: Vec<&String> = rows.iter().filter_map(|(_, record)| record.get(column)).filter(|field| !field.is_empty()).collect();
+
+            match fields.iter().map(|field| infer(field)).reduce(widen) {
+                None => 0,
+                Some(Type::Text) => {
+                    let lengths: IndexSet<usize> = fields.iter().map(|field| field.chars().count()).collect();
+
+                    match lengths.len() {
+                        1 if lengths[0] != name.chars().count() => 1,
+                        1 => -1,
+                        _ => 0,
+                    }
+                }
+                Some(_) => 1,
+            }
+        })
+        .sum();
+
+    votes > 0
+}
+
+/// Parses a table. With a header, each record becomes a dict keyed by the column names, and otherwise a list.
+pub async fn parse_table(chunks: Chunks, delimiter: u8, mut options: Options) -> Result<Value, RuntimeError> {
+    let mut reader = TableReader::new(chunks, delimiter);
+
+    let Some(first) = reader.next().await? else {
+        reader.chunks.finish().await?;
+        return Ok(options.records.finish());
+    };
+
+    // The first record is sampled along with the rest, as whether it's a header depends on how it compares to them
+    let mut sample = vec![first];
+
+    while sample.len() <= SAMPLE {
+        match reader.next().await? {
+            Some(record) => sample.push(record),
+            None => break,
+        }
+    }
+
+    let header = match options.header.unwrap_or_else(|| is_header(&sample[0].1, &sample[1..])) {
+        true => Some(sample.remove(0).1),
+        false => None,
+    };
+
+    let mut types: Vec<Option<Type>> = vec![];
+
+    for (_, record) in sample.iter().filter(|_| !options.raw) {
+        types.resize(types.len().max(record.len()), None);
+
+        for (column, field) in record.iter().enumerate().filter(|(_, field)| !field.is_empty()) {
+            types[column] = Some(types[column].map_or(infer(field), |column| widen(column, infer(field))));
+        }
+    }
+
+    let format = reader.format;
+    let row = |(offset, record): (usize, Vec<String>)| -> Result<Value, RuntimeError> {
+        let fields = record.into_iter().enumerate().map(|(column, field)| match options.raw {
+            true => Value::String(field),
+            false => convert(field, types.get(column).copied().flatten()),
+        });
+
+        match &header {
+            Some(header) if header.len() != fields.len() => Err(RuntimeError::ParseError(format.to_owned(), offset,
+                format!("the record has {} fields but the header has {}", fields.len(), header.len()))),
+            Some(header) => Ok(Value::Dict(header.iter().cloned().zip(fields).collect::<Dict>())),
+            None => Ok(Value::List(fields.collect())),
+        }
+    };
+
+    for record in sample {
+        options.records.push(row(record)?).await?;
+    }
+
+    while let Some(record) = reader.next().await? {
+        options.records.push(row(record)?).await?;
+    }
+
+    reader.chunks.finish().await?;
+    Ok(options.records.finish())
+}
+
+/// A value as a field. Text is written as it is and null as nothing, while anything else is written as it would be
+/// in JSON.
+fn field(function: &str, value: &Value) -> Result<String, RuntimeError> {
+    match value {
+        Value::Null => Ok(String::new()),
+        Value::String(str) => Ok(str.clone()),
+        value => match serde_json::to_value(value) {
+            Ok(serde_json::Value::String(str)) => Ok(str),
+            Ok(json) => Ok(json.to_string()),
+            Err(err) => Err(RuntimeError::InvalidArgument(function.to_owned(), err.to_string())),
+        },
+    }
+}
+
+/// Writes a list of dicts as a table with a header naming their keys, in the order they're first seen. A list of lists
+/// is written as records without a header.
+pub fn write_table(function: &str, value: &Value, delimiter: u8) -> Result<String, RuntimeError> {
+    let Value::List(rows) = value else {
+        return Err(RuntimeError::InvalidArgument(function.to_owned(), format!("expected list, got {}", value.type_name())));
+    };
+
+    let mut writer = csv::WriterBuilder::new().delimiter(delimiter).flexible(true).from_writer(vec![]);
+    let write = |writer: &mut csv::Writer<Vec<u8>>, record: Vec<String>| writer.write_record(record)
+        .map_err(|err| RuntimeError::IoError(err.to_string()));
+
+    let dicts: Vec<&Dict> = rows.iter().filter_map(|row| match row {
+        Value::Dict(dict) => Some(dict),
+        _ => None,
+    }).collect();
+
+    if !rows.is_empty() && dicts.len() == rows.len() {
+        let columns: IndexSet<&String> = dicts.iter().flat_map(|dict| dict.keys()).collect();
+        write(&mut writer, columns.iter().map(|column| column.to_string()).collect())?;
+
+        for dict in dicts {
+            write(&mut writer, columns.iter().map(|column| dict.get(*column).map_or(Ok(String::new()), |value| field(function, value))).collect::<Result<_, _>>()?)?;
+        }
+    } else {
+        for row in rows {
+            match row {
+                Value::List(list) => write(&mut writer, list.iter().map(|value| field(function, value)).collect::<Result<_, _>>()?)?,
+                value => return Err(RuntimeError::InvalidArgument(function.to_owned(), format!("expected every row to be a dict, or every row a list, got {}", value.type_name()))),
+            }
+        }
+    }
+
+    let table = writer.into_inner().map_err(|err| RuntimeError::IoError(err.to_string()))?;
+    Ok(String::from_utf8(table).unwrap_or_default())
+}
+
+/// Parses CSV into a list of records. Unless `header` says whether the first record names the columns, it's taken to if
+/// it stands out from the records after it, as `is_header` decides. The type of each column is inferred from the records
+/// at the start of the table, unless `raw` is set. Streams are read a chunk at a time, and with `each`, every record is passed to it as
+/// it's read instead of being kept.
+fn csv(args: Arguments) -> BuiltinResult {
+    Box::pin(async move {
+        let chunks = Chunks::new("csv", args.require("csv", "text", 0)?.clone())?;
+        parse_table(chunks, b',', Options::new("csv", &args)?).await
+    })
+}
+
+/// Parses tab-separated values, as `csv` does
+fn tsv(args: Arguments) -> BuiltinResult {
+    Box::pin(async move {
+        let chunks = Chunks::new("tsv", args.require("tsv", "text", 0)?.clone())?;
+        parse_table(chunks, b'\t', Options::new("tsv", &args)?).await
+    })
+}
+
+fn to_csv(args: Arguments) -> BuiltinResult {
+    Box::pin(async move {
+        write_table("to_csv", &resolve(args.require("to_csv", "rows", 0)?.clone()).await?, b',').map(Value::String)
+    })
+}
+
+fn to_tsv(args: Arguments) -> BuiltinResult {
+    Box::pin(async move {
+        write_table("to_tsv", &resolve(args.require("to_tsv", "rows", 0)?.clone()).await?, b'\t').map(Value::String)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::command::eval::run;
+    use crate::command::runtime_err::RuntimeError;
+    use crate::command::scope::Environment;
+    use crate::command::value::Value;
+
+    #[tokio::test]
+    pub async fn test_csv() -> Result<(), RuntimeError> {
+        let env = Environment::new();
+        env.define("table", Value::String("name,age,height,admin,postcode,notes\nAda,36,1.65,true,0123,\"likes, commas\"\nBob,41,2,FALSE,4000,\n".to_owned()));
+
+        assert_eq!(run(&env, "(table | csv).0").await?.to_string(), "{ name: 'Ada', age: 36, height: 1.65, admin: true, postcode: '0123', notes: 'likes, commas' }");
+        assert_eq!(run(&env, "(table | csv).1").await?.to_string(), "{ name: 'Bob', age: 41, height: 2.0, admin: false, postcode: '4000', notes: null }");
+        assert_eq!(run(&env, "(table | csv(raw: true)).1.age").await?.to_string(), "'41'");
+
+        // A first record holding numbers is data rather than a header, unless it's said to be one
+        assert_eq!(run(&env, "csv('1,a\\n2,b')").await?.to_string(), "{ { 1, 'a' }, { 2, 'b' } }");
+        assert_eq!(run(&env, "csv('1,a\\n2,b', header: true)").await?.to_string(), "{ { '1': 2, a: 'b' } }");
+        assert_eq!(run(&env, "csv('a,b\\nc,d', header: false) | len").await?.to_string(), "2");
+        assert_eq!(run(&env, "tsv('id\\tcount\\nx\\t3')").await?.to_string(), "{ { id: 'x', count: 3 } }");
+
+        // Text throughout is only a header if it stands out from the records below it
+        assert_eq!(run(&env, "csv('Ada,London\\nBob,Paris')").await?.to_string(), "{ { 'Ada', 'London' }, { 'Bob', 'Paris' } }");
+        assert_eq!(run(&env, "csv('code,city\\nGB,London\\nFR,Paris')").await?.to_string(), "{ { code: 'GB', city: 'London' }, { code: 'FR', city: 'Paris' } }");
+        assert_eq!(run(&env, "csv('a,b') | len").await?.to_string(), "1");
+        assert_eq!(run(&env, "csv('')").await?.to_string(), "{}");
+
+        assert_eq!(run(&env, "try { csv('a,b\\n1,2\\n3') } catch err { err.message }").await?.to_string(), "'Invalid CSV at byte 8: the record has 1 fields but the header has 2'");
+        assert!(matches!(run(&env, "csv('a', header: 1)").await, Err(RuntimeError::InvalidArgument(..))));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    pub async fn test_csv_streams() -> Result<(), RuntimeError> {
+        let env = Environment::new();
+
+        // Records far larger than a chunk, and more of them than are sampled for types
+        assert_eq!(run(&env, "sh('-c', 'echo n,text; echo 0,$(printf %020000d 0); seq 1 1500 | sed s/$/,x/') | csv | len").await?.to_string(), "1501");
+
+        run(&env, "count = 0").await?;
+        assert_eq!(run(&env, "sh('-c', 'echo n; seq 1 2000') | csv(each: row -> count = count + row.n)").await?.to_string(), "null");
+        assert_eq!(run(&env, "count").await?.to_string(), "2001000");
+
+        assert!(matches!(run(&env, "sh('-c', 'echo a; exit 2') | csv").await, Err(RuntimeError::ExitError(_, 2))));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    pub async fn test_write_tables() -> Result<(), RuntimeError> {
+        let env = Environment::new();
+        env.define("nothing", Value::Null);
+
+        assert_eq!(run(&env, "to_csv({ { name: 'Ada', tags: { 'a', 'b' } }, { name: 'Bob, Jr', age: 41 } })").await?.to_string(),
+            "'name,tags,age\nAda,\"[\"\"a\"\",\"\"b\"\"]\",\nBob, Jr,,41\n'".replace("Bob, Jr", "\"Bob, Jr\""));
+        assert_eq!(run(&env, "to_tsv({ { 1, nothing, 2.5 } })").await?.to_string(), "'1\t\t2.5\n'");
+        assert!(matches!(run(&env, "to_csv({ { a: 1 }, 2 })").await, Err(RuntimeError::InvalidArgument(..))));
+
+        // Tables survive being written to a file and read back
+        run(&env, "write_file({ { n: 1, ok: true } }, 'mem:/test-tables.csv', format: 'csv')").await?;
+        assert_eq!(run(&env, "read_file('mem:/test-tables.csv')").await?.to_string(), "'n,ok\n1,true\n'");
+        assert_eq!(run(&env, "read_file('mem:/test-tables.csv', format: 'csv')").await?.to_string(), "{ { n: 1, ok: true } }");
+
+        Ok(())
+    }
+}
@@ -1,143 +1,1108 @@
-use std::collections::VecDeque;
+use std::collections::HashSet;
 use std::future::Future;
 use std::pin::Pin;
-use std::task::{Context, Poll};
 
-use futures::{Stream, StreamExt};
+use futures::stream::{self, LocalBoxStream};
+use futures::StreamExt;
+use nix::unistd::{access, AccessFlags};
 
-use crate::command::parser::{ASTNode, LiteralToken, OperatorType, OpOrExpr, SyntaxError};
-use crate::command::proc::ProcessOptions;
+use crate::command::builtins;
+use crate::command::builtins::Arguments;
+use crate::command::location::Location;
+use crate::command::module;
+use crate::command::ops;
+use crate::command::parser::{ASTNode, DictKey, FormatPart, KeyOrNoKey, LiteralToken, OperatorType, PipeType, SyntaxError};
+use crate::command::proc::{ChildProcess, ProcessOptions};
+use crate::command::runtime_err::RuntimeError;
+use crate::command::scope::Environment;
+use crate::command::stream::ByteStream;
+use crate::command::value::{Captured, Dict, Function, Value};
 
-#[derive(Debug, Clone)]
-pub struct ByteStream {
-    from_string: String,
+pub fn eval(ast: Box<ASTNode>, env: Environment, options: ProcessOptions) -> Pin<Box<dyn Future<Output=Result<Value, RuntimeError>>>> {
+    Box::pin(async move {
+        match *ast.clone() {
+            ASTNode::Call(function, args, span) => async {
+                let callee = eval(function, env.clone(), ProcessOptions { resolve_names_to_executables: true, ..options.clone() }).await?;
+                call(callee, args, None, PipeType::Stdout, env, options).await
+            }.await.map_err(|err| err.at(span)),
+            ASTNode::Literal(lit, span) => eval_literal(lit, &env, &options).await.map_err(|err| err.at(span)),
+            ASTNode::Unary(op, operand) => ops::unary(op, eval(operand, env, options).await?),
+            ASTNode::Binary(OperatorType::Assign, target, value) => {
+                let name = symbol_name(&target)
+                    .ok_or_else(|| RuntimeError::InvalidOperation("only names can be assigned to".to_owned()))?
+                    .to_owned();
+
+                let value = eval(value, env.clone(), options).await?;
+                env.assign(&name, value);
+
+                Ok(Value::Null)
+            }
+            ASTNode::Binary(OperatorType::Pipe(_), _, _) => eval_pipeline(ast, env, options).await,
+            // The logical operators only evaluate their right-hand side if the left doesn't already decide the result
+            ASTNode::Binary(OperatorType::And, lhs, rhs) => Ok(Value::Boolean(
+                eval(lhs, env.clone(), options.clone()).await?.truthy() && eval(rhs, env, options).await?.truthy()
+            )),
+            ASTNode::Binary(OperatorType::Or, lhs, rhs) => Ok(Value::Boolean(
+                eval(lhs, env.clone(), options.clone()).await?.truthy() || eval(rhs, env, options).await?.truthy()
+            )),
+            ASTNode::Binary(op, lhs, rhs) => {
+                let lhs = eval(lhs, env.clone(), options.clone()).await?;
+                let rhs = eval(rhs, env, options).await?;
+                ops::binary(op, lhs, rhs)
+            }
+            ASTNode::Lambda(args, body) => Ok(Value::Function(Function::Lambda(args, body, Captured::Strong(env), None))),
+            ASTNode::Dict(entries) => eval_dict(entries, env, options).await,
+            ASTNode::Index(parts) => eval_index(&parts, None, env, options).await,
+            ASTNode::Block(statements) => {
+                let scope = env.child();
+                let mut value = Value::Null;
+
+                for statement in statements {
+                    value = eval(statement, scope.clone(), options.clone()).await?;
+                }
+
+                Ok(value)
+            }
+            ASTNode::If(condition, body, alternative) => if eval(condition, env.clone(), options.clone()).await?.truthy() {
+                eval(body, env, options).await
+            } else {
+                match alternative {
+                    Some(alternative) => eval(alternative, env, options).await,
+                    None => Ok(Value::Null)
+                }
+            },
+            ASTNode::For(name, iterable, body) => {
+                let mut items = iterate(eval(iterable, env.clone(), options.clone()).await?)?;
+
+                while let Some(item) = items.next().await {
+                    let scope = env.child();
+                    scope.define(&name, item);
+                    eval(body.clone(), scope, options.clone()).await?;
+                }
+
+                Ok(Value::Null)
+            }
+            ASTNode::Function(name, params, body) => {
+                env.define(&name, Value::Function(Function::Lambda(params, body, Captured::Weak(env.downgrade()), Some(name.clone()))));
+                Ok(Value::Null)
+            }
+            ASTNode::Return(value) => Err(RuntimeError::Return(Box::new(match value {
+                Some(value) => eval(value, env, options).await?,
+                None => Value::Null
+            }))),
+            // Errors are caught once the body has finished, which for a process means once it has exited
+            ASTNode::Try(body, name, handler) => match settle(eval(body, env.clone(), options.clone()).await).await {
+                Err(err) if !matches!(err.root(), RuntimeError::Return(_) | RuntimeError::Cancelled | RuntimeError::OutputClosed) => {
+                    let scope = env.child();
+
+                    if let Some(name) = name {
+                        scope.define(&name, err.to_value());
+                    }
+
+                    eval(handler, scope, options).await
+                }
+                result => result
+            },
+            ASTNode::Invalid(span) => Err(RuntimeError::Syntax(SyntaxError::InvalidSyntax(span))),
+            ASTNode::Nothing => Ok(Value::Null),
+            ASTNode::Import(names, module) => {
+                let module = match eval(module, env.clone(), options).await? {
+                    Value::String(module) => module,
+                    Value::Location(location) => match location.local_path() {
+                        Some(path) => path.to_string_lossy().into_owned(),
+                        None => return Err(RuntimeError::ImportError(location.to_string(), "modules can only be imported from the local filesystem".to_owned()))
+                    },
+                    value => return Err(RuntimeError::InvalidOperation(format!("cannot import a module from {}", value.type_name())))
+                };
+
+                let bindings = module::import(&module).await?;
+
+                if names.is_empty() {
+                    for (name, value) in &bindings {
+                        env.define(name, value.clone());
+                    }
+                }
+
+                for name in names {
+                    let value = bindings.get(&name)
+                        .ok_or_else(|| RuntimeError::ImportError(module.clone(), format!("'{}' is not defined by the module", name)))?;
+
+                    env.define(&name, value.clone());
+                }
+
+                Ok(Value::Null)
+            }
+        }
+    })
 }
 
-impl Stream for ByteStream {
-    type Item = Vec<u8>;
+/// Parses and evaluates a line of source in the given scope, which is how tests run code. Errors are given without where
+/// they were raised, so that tests can compare them directly.
+#[cfg(test)]
+pub async fn run(env: &Environment, source: &str) -> Result<Value, RuntimeError> {
+    use crate::command::parser::{parse, tokenise};
 
-    fn poll_next(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        if !self.from_string.is_empty() {
-            let c = self.from_string.clone();
-            self.from_string = String::new();
-            Poll::Ready(Some(c.as_bytes().to_vec()))
+    eval(parse(&tokenise(source)?)?, env.clone(), Default::default()).await.map_err(RuntimeError::unplaced)
+}
+
+/// Resolves names through the scope chain first. Names in the callee position of a call then fall back to builtins and
+/// executables on the `PATH`, and other names to environment variables.
+async fn eval_literal(literal: LiteralToken, env: &Environment, options: &ProcessOptions) -> Result<Value, RuntimeError> {
+    match literal {
+        LiteralToken::Symbol(name) => if let Some(value) = env.get(&name) {
+            Ok(value)
+        } else if options.resolve_names_to_executables {
+            if let Some(builtin) = builtins::lookup(&name) {
+                Ok(Value::Function(Function::Builtin(builtin)))
+            } else {
+                match locate_binary(&name) {
+                    Some(binary) => Ok(Value::Location(binary)),
+                    None => Err(RuntimeError::NoValue(name))
+                }
+            }
         } else {
-            Poll::Ready(None)
+            std::env::var(&name)
+                .map(Value::String)
+                .map_err(|_| RuntimeError::NoValue(name))
+        },
+        LiteralToken::String(str) => Ok(Value::String(str)),
+        LiteralToken::Boolean(bool) => Ok(Value::Boolean(bool)),
+        LiteralToken::Integer(int) => Ok(Value::Integer(int)),
+        LiteralToken::Number(num) => Ok(Value::Float(num)),
+        LiteralToken::Regex(regex) => Ok(Value::Regex(regex)),
+        LiteralToken::Bytes(bytes) => Ok(Value::Bytes(bytes)),
+        LiteralToken::Format(parts) => interpolate(parts, env, options).await,
+    }
+}
+
+/// Evaluates an f-string. Values are converted to text as they would be to pass to a process, except that the line break
+/// at the end of a process's output is dropped, as with `$(...)` in other shells.
+async fn interpolate(parts: Vec<FormatPart>, env: &Environment, options: &ProcessOptions) -> Result<Value, RuntimeError> {
+    let mut text = String::new();
+
+    for part in parts {
+        match part {
+            FormatPart::Text(str) => text += &str,
+            FormatPart::Expression(expr) => {
+                let value = eval(expr, env.clone(), options.clone()).await?;
+                let output = matches!(value, Value::Stream(_));
+                let str = to_argument(value).await?;
+
+                text += if output { str.strip_suffix('\n').unwrap_or(&str) } else { &str };
+            }
         }
     }
+
+    Ok(Value::String(text))
 }
 
-impl ByteStream {
-    pub async fn merge(mut self) -> Vec<u8> {
-        let mut vec = vec![];
+/// Evaluates a brace literal. Literals with keys evaluate to dicts and those without to lists, as `keys` prints them.
+/// Spreading a dict merges its entries into the literal, while spreading a list splices in its items. A literal made up
+/// only of spreads takes the type of what is spread into it.
+async fn eval_dict(entries: Vec<DictKey>, env: Environment, options: ProcessOptions) -> Result<Value, RuntimeError> {
+    let mut is_dict = match entries.iter().find(|i| !matches!(i, DictKey::Spread(_))) {
+        Some(entry) => Some(matches!(entry, DictKey::Key(..))),
+        None if entries.is_empty() => Some(true),
+        None => None,
+    };
+
+    let mut dict = Dict::new();
+    let mut list = vec![];
+    // Keys written out in the literal may only appear once, though they may replace keys which were spread in
+    let mut written = HashSet::new();
+
+    for entry in entries {
+        match entry {
+            DictKey::Key(_, _) | DictKey::NoKey(_) if is_dict != Some(matches!(entry, DictKey::Key(..))) =>
+                return Err(RuntimeError::InvalidOperation("cannot mix keyed and unkeyed entries".to_owned())),
+            DictKey::Key(key, value) => {
+                let key = dict_key(key, &env, &options).await?;
+                let value = eval(value, env.clone(), options.clone()).await?;
+
+                if !written.insert(key.clone()) {
+                    return Err(RuntimeError::DuplicateKey(key));
+                }
+
+                ops::merge(&mut dict, Dict::from([(key, value)]));
+            }
+            DictKey::NoKey(value) => list.push(eval(value, env.clone(), options.clone()).await?),
+            DictKey::Spread(value) => {
+                let value = eval(value, env.clone(), options.clone()).await?;
+
+                match (*is_dict.get_or_insert(matches!(value, Value::Dict(_))), value) {
+                    (true, Value::Dict(spread)) => ops::merge(&mut dict, spread),
+                    (false, Value::List(spread)) => list.extend(spread),
+                    (is_dict, value) => return Err(RuntimeError::InvalidOperation(format!("cannot spread {} into a {}", value.type_name(), if is_dict { "dict" } else { "list" }))),
+                }
+            }
+        }
+    }
+
+    Ok(if is_dict == Some(true) { Value::Dict(dict) } else { Value::List(list) })
+}
+
+/// Bare names used as keys stand for themselves, as in `{ name: 'John Doe' }`. Any other key is evaluated.
+async fn dict_key(key: Box<ASTNode>, env: &Environment, options: &ProcessOptions) -> Result<String, RuntimeError> {
+    if let Some(name) = symbol_name(&key) {
+        return Ok(name.to_owned());
+    }
+
+    match eval(key, env.clone(), options.clone()).await? {
+        Value::String(key) => Ok(key),
+        value @ (Value::Integer(_) | Value::Float(_) | Value::Boolean(_)) => Ok(value.to_string()),
+        value => Err(RuntimeError::InvalidOperation(format!("cannot use {} as a dict key", value.type_name()))),
+    }
+}
+
+/// The items a `for` loop runs over: the items of a list, the keys of a dict, or the lines of a string or stream. Streams
+/// are read a line at a time, so loops over process output start before the process exits.
+fn iterate(value: Value) -> Result<LocalBoxStream<'static, Value>, RuntimeError> {
+    match value {
+        Value::List(list) => Ok(stream::iter(list).boxed_local()),
+        Value::Dict(dict) => Ok(stream::iter(dict.into_keys().map(Value::String)).boxed_local()),
+        Value::String(str) => Ok(stream::iter(str.lines().map(|line| Value::String(line.to_owned())).collect::<Vec<_>>()).boxed_local()),
+        Value::Stream(stream) => Ok(stream::unfold((stream, Vec::new()), |(mut stream, mut buffer)| async move {
+            loop {
+                if let Some(end) = buffer.iter().position(|b| *b == b'\n') {
+                    let line = String::from_utf8_lossy(&buffer[..end]).into_owned();
+                    buffer.drain(..=end);
+                    return Some((Value::String(line), (stream, buffer)));
+                }
+
+                match stream.next().await {
+                    Some(chunk) => buffer.extend(chunk),
+                    None if buffer.is_empty() => return None,
+                    None => return Some((Value::String(String::from_utf8_lossy(&std::mem::take(&mut buffer)).into_owned()), (stream, buffer))),
+                }
+            }
+        }).boxed_local()),
+        value => Err(RuntimeError::InvalidOperation(format!("cannot iterate over {}", value.type_name()))),
+    }
+}
+
+/// Evaluates `base.key.key...`. A leading dot stands for `input`, the value piped into the stage.
+async fn eval_index(parts: &[Box<ASTNode>], input: Option<Value>, env: Environment, options: ProcessOptions) -> Result<Value, RuntimeError> {
+    let mut parts = parts.iter().cloned();
+
+    let mut value = match parts.next() {
+        Some(base) if matches!(*base, ASTNode::Nothing) => input.ok_or_else(|| RuntimeError::InvalidOperation("a leading '.' needs a value piped into it".to_owned()))?,
+        Some(base) => eval(base, env.clone(), options.clone()).await?,
+        None => return Err(RuntimeError::Syntax(SyntaxError::UnexpectedEOF())),
+    };
+
+    for key in parts {
+        value = index(value, eval(key, env.clone(), options.clone()).await?)?;
+    }
+
+    Ok(value)
+}
+
+/// Looks a single key up in a value. Lists and strings are indexed by position, counting back from the end for negative
+/// positions, and dicts by key.
+pub fn index(value: Value, key: Value) -> Result<Value, RuntimeError> {
+    let position = |index: i64, len: usize| -> Result<usize, RuntimeError> {
+        let position = if index < 0 { len as i64 + index } else { index };
+
+        match usize::try_from(position) {
+            Ok(position) if position < len => Ok(position),
+            _ => Err(RuntimeError::IndexOutOfRange(index, len))
+        }
+    };
 
-        while let Some(i) = self.next().await {
-            vec.extend(i)
+    match (value, key) {
+        (Value::Dict(mut dict), Value::String(key)) => dict.swap_remove(&key).ok_or(RuntimeError::MissingKey(key)),
+        (Value::Dict(mut dict), Value::Integer(key)) => dict.swap_remove(&key.to_string()).ok_or_else(|| RuntimeError::MissingKey(key.to_string())),
+        // The first key a regex matches, which is how `headers.i'content-type'` finds a key in any case
+        (Value::Dict(dict), Value::Regex(regex)) => dict.into_iter()
+            .find_map(|(key, value)| regex.is_match(&key).then_some(value))
+            .ok_or_else(|| RuntimeError::MissingKey(regex.to_string())),
+        (Value::List(mut list), Value::Integer(index)) => Ok(list.swap_remove(position(index, list.len())?)),
+        (Value::String(str), Value::Integer(index)) => {
+            let chars: Vec<char> = str.chars().collect();
+            Ok(Value::String(chars[position(index, chars.len())?].to_string()))
         }
+        (Value::Bytes(bytes), Value::Integer(index)) => Ok(Value::Integer(bytes[position(index, bytes.len())?] as i64)),
+        (value, key) => Err(RuntimeError::InvalidOperation(format!("cannot index {} with {}", value.type_name(), key.type_name()))),
+    }
+}
 
-        vec
+/// The name referred to by a node consisting of a single bare symbol.
+fn symbol_name(node: &ASTNode) -> Option<&str> {
+    match node {
+        ASTNode::Literal(LiteralToken::Symbol(name), _) => Some(name),
+        _ => None
     }
+}
+
+/// Invokes `callee` with the given arguments. When the call is a pipeline stage, `input` holds the upstream value, which
+/// becomes the standard input of a process or the first argument of a builtin. `output` selects which of a process's
+/// output streams is captured.
+async fn call(callee: Value, args: Vec<KeyOrNoKey>, input: Option<Value>, output: PipeType, env: Environment, options: ProcessOptions) -> Result<Value, RuntimeError> {
+    match callee {
+        Value::Location(binary) => {
+            let mut argv = Vec::with_capacity(args.len());
 
-    pub fn from_string(s: &str) -> Self {
-        ByteStream {
-            from_string: s.to_owned()
+            for arg in args {
+                match arg {
+                    KeyOrNoKey::NoKey(value) => argv.push(to_argument(eval(value, env.clone(), options.clone()).await?).await?),
+                    KeyOrNoKey::Key(key, value) => argv.push(format!("--{}={}", key, to_argument(eval(value, env.clone(), options.clone()).await?).await?)),
+                }
+            }
+
+            spawn(&binary, &argv, input.map(Value::into_stream), output, options)
+        }
+        callee => {
+            let mut arguments = Arguments::default();
+            arguments.positional.extend(input);
+
+            for arg in args {
+                match arg {
+                    KeyOrNoKey::NoKey(value) => arguments.positional.push(eval(value, env.clone(), options.clone()).await?),
+                    KeyOrNoKey::Key(key, value) => {
+                        arguments.named.insert(key, eval(value, env.clone(), options.clone()).await?);
+                    }
+                }
+            }
+
+            invoke(callee, arguments).await
         }
     }
 }
 
-pub fn eval(ast: Box<ASTNode>, options: ProcessOptions) -> Pin<Box<dyn Future<Output=Result<ByteStream, SyntaxError>>>> {
+/// Calls a function value with arguments which have already been evaluated. This is how builtins call back into
+/// lambdas they were given.
+pub fn invoke(callee: Value, arguments: Arguments) -> Pin<Box<dyn Future<Output=Result<Value, RuntimeError>>>> {
     Box::pin(async move {
-        match *ast.clone() {
-            ASTNode::Call(function, args) => {
-                let executable = eval(function, ProcessOptions { resolve_names_to_executables: true, ..options.clone() }).await?;
+        match callee {
+            Value::Function(Function::Builtin(builtin)) => (builtin.function)(arguments).await,
+            Value::Function(Function::Lambda(params, body, captured, name)) => {
+                if arguments.positional.len() + arguments.named.len() > params.len() {
+                    return Err(RuntimeError::InvalidArgument("lambda".to_owned(), format!("expected {} arguments, got {}", params.len(), arguments.positional.len() + arguments.named.len())));
+                }
+
+                let scope = match captured {
+                    Captured::Strong(captured) => captured.child(),
+                    // Only the binding in the captured scope itself is weak, so that scope must still be around
+                    Captured::Weak(captured) => captured.upgrade().expect("function outlived the scope holding it").child(),
+                };
+                let mut positional = arguments.positional.into_iter();
+
+                for param in params {
+                    let value = arguments.named.get(&param).cloned()
+                        .or_else(|| positional.next())
+                        .ok_or_else(|| RuntimeError::InvalidArgument("lambda".to_owned(), format!("missing argument '{}'", param)))?;
+
+                    scope.define(&param, value);
+                }
+
+                match eval(body, scope, ProcessOptions::default()).await {
+                    Err(RuntimeError::Return(value)) => Ok(*value),
+                    Err(err) => Err(RuntimeError::InFunction(name.unwrap_or_else(|| "<lambda>".to_owned()), Box::new(err))),
+                    result => result
+                }
+            }
+            Value::Location(binary) => {
+                let mut argv = Vec::with_capacity(arguments.positional.len() + arguments.named.len());
+
+                for value in arguments.positional {
+                    argv.push(to_argument(value).await?);
+                }
 
-                let data = executable
-                    .merge()
-                    .await;
-                let data = String::from_utf8_lossy(&data);
+                for (key, value) in arguments.named {
+                    argv.push(format!("--{}={}", key, to_argument(value).await?));
+                }
 
-                println!("Data: {:?}", data);
+                spawn(&binary, &argv, None, PipeType::Stdout, ProcessOptions::default())
             }
-            ASTNode::Expression(expr) => {
-                // Implement the Shunting-Yard algorithm to correctly evaluate operations
+            value => Err(RuntimeError::NotCallable(value.type_name().to_owned()))
+        }
+    })
+}
 
-                let mut opstack = VecDeque::<OpOrExpr>::new();
-                let mut output = VecDeque::<OpOrExpr>::new();
+/// Runs each stage of a chain of pipes in turn. Every stage is started before the next one, so processes connected by
+/// pipes run concurrently.
+async fn eval_pipeline(node: Box<ASTNode>, env: Environment, options: ProcessOptions) -> Result<Value, RuntimeError> {
+    // Pipes are left-associative, so the stages are found by walking down the left-hand side
+    let mut stages = vec![];
+    let mut node = node;
+    let mut output = PipeType::Stdout;
 
-                for token in expr {
-                    match token {
-                        OpOrExpr::Operator(op) => {
-                            while let Some(OpOrExpr::Operator(top)) = opstack.back() {
-                                if top.precedence() > op.precedence() {
-                                    break;
-                                }
+    while let ASTNode::Binary(OperatorType::Pipe(pipe), lhs, rhs) = *node {
+        stages.push((rhs, output));
+        output = pipe;
+        node = lhs;
+    }
 
-                                output.push_back(OpOrExpr::Operator(*top));
-                            }
+    stages.push((node, output));
 
-                            opstack.push_back(OpOrExpr::Operator(op));
-                        }
-                        OpOrExpr::Expr(expr) => output.push_back(OpOrExpr::Expr(expr)),
-                        OpOrExpr::Literal(lit) => output.push_back(OpOrExpr::Literal(lit))
-                    }
+    let mut input = None;
+
+    for (stage, output) in stages.into_iter().rev() {
+        input = Some(eval_stage(stage, input, output, env.clone(), options.clone()).await?);
+    }
+
+    Ok(input.unwrap_or(Value::Null))
+}
+
+async fn eval_stage(stage: Box<ASTNode>, input: Option<Value>, output: PipeType, env: Environment, options: ProcessOptions) -> Result<Value, RuntimeError> {
+    let executable = ProcessOptions { resolve_names_to_executables: true, ..options.clone() };
+
+    let (value, span) = match stage.as_ref() {
+        ASTNode::Call(function, args, span) => {
+            let result = async {
+                let callee = eval(function.clone(), env.clone(), executable).await?;
+                call(callee, args.clone(), input, output, env, options).await
+            }.await;
+
+            return result.map_err(|err| err.at(*span));
+        }
+        ASTNode::Index(parts) if matches!(parts.first().map(Box::as_ref), Some(ASTNode::Nothing)) =>
+            return eval_index(parts, input, env.clone(), options).await,
+        // A bare name after a pipe is called with the upstream value alone, as in `readdir() | keys`
+        ASTNode::Literal(LiteralToken::Symbol(name), span) if input.is_some() =>
+            (eval_literal(LiteralToken::Symbol(name.clone()), &env, &executable).await.map_err(|err| err.at(*span))?, Some(*span)),
+        _ => (eval(stage, env.clone(), options.clone()).await?, None)
+    };
+
+    match input {
+        None => Ok(value),
+        input => call(value, vec![], input, output, env, options).await.map_err(|err| match span {
+            Some(span) => err.at(span),
+            None => err,
+        }),
+    }
+}
+
+/// Runs the program at `binary`, which has to be on the local filesystem for the operating system to find it.
+fn spawn(binary: &Location, argv: &[String], stdin: Option<ByteStream>, output: PipeType, options: ProcessOptions) -> Result<Value, RuntimeError> {
+    let path = binary.local_path()
+        .ok_or_else(|| RuntimeError::SpawnError(binary.to_string(), "only programs on the local filesystem can be run".to_owned()))?;
+
+    ChildProcess::spawn(&path.to_string_lossy(), argv, stdin, output, options)
+        .map(|child| Value::Stream(child.into_output()))
+        .map_err(|err| RuntimeError::SpawnError(binary.path().to_owned(), err.to_string()))
+}
+
+/// Converts a value into the text of a single command-line argument. Locations on the local filesystem become plain
+/// paths, which is what other programs understand.
+async fn to_argument(value: Value) -> Result<String, RuntimeError> {
+    match value {
+        Value::String(str) => Ok(str),
+        Value::Location(location) => Ok(location.local_path().map_or_else(|| location.to_string(), |path| path.to_string_lossy().into_owned())),
+        Value::Stream(stream) => {
+            let output = String::from_utf8_lossy(&stream.clone().merge().await).into_owned();
+            check_exit(&stream).await?;
+            Ok(output)
+        }
+        value => Ok(value.to_string()),
+    }
+}
+
+/// Reads a process's output in full so that its exit status is known, leaving the output to be read again.
+async fn settle(result: Result<Value, RuntimeError>) -> Result<Value, RuntimeError> {
+    match result? {
+        Value::Stream(stream) => {
+            let output = stream.clone().merge().await;
+            check_exit(&stream).await?;
+            Ok(Value::Stream(ByteStream::from_bytes(output)))
+        }
+        value => Ok(value),
+    }
+}
+
+/// Waits for the process writing to a stream to exit, failing if it exits unsuccessfully or if the stream couldn't be
+/// read to the end. Streams which don't come from a process otherwise succeed.
+pub async fn check_exit(stream: &ByteStream) -> Result<(), RuntimeError> {
+    match stream.exit_status().await {
+        Some((name, status)) if status != 0 => Err(RuntimeError::ExitError(name, status)),
+        _ => stream.error().map_or(Ok(()), |err| Err(RuntimeError::IoError(err.to_string()))),
+    }
+}
+
+fn path_dirs() -> Vec<String> {
+    std::env::var("PATH")
+        .unwrap_or_else(|_| "/usr/local/bin:/usr/bin:/bin".to_string())
+        .split(':')
+        .map(str::to_owned)
+        .collect()
+}
+
+/// Finds the executable called `hint` in one of the directories on the `PATH`. This happens for every name looked up as
+/// a command, so each candidate costs one `stat`, to see that it's a file, and one `access` check, to see that it can be
+/// run. Building a `Metadata` would also look up the owner and group by name and make three `access` checks.
+pub fn locate_binary(hint: &str) -> Option<Location> {
+    path_dirs().into_iter()
+        .map(|dir| Location::local(dir).join(hint))
+        .find(|location| location.local_path().is_some_and(|path| {
+            std::fs::metadata(&path).is_ok_and(|metadata| metadata.is_file()) && access(&path, AccessFlags::X_OK).is_ok()
+        }))
+}
+
+/// The names of the executables on the `PATH` starting with `prefix`, for completion.
+pub fn executables(prefix: &str) -> Vec<String> {
+    path_dirs().into_iter()
+        .filter_map(|dir| std::fs::read_dir(dir).ok())
+        .flatten()
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .filter(|name| name.starts_with(prefix))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    pub async fn test_spawn_process() -> Result<(), RuntimeError> {
+        let env = Environment::new();
+
+        match run(&env, "echo('hello world', 2, level: 3)").await? {
+            Value::Stream(stream) => assert_eq!(stream.merge().await, b"hello world 2 --level=3\n"),
+            value => panic!("Expected stream, got {}", value)
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    pub async fn test_process_pipeline() -> Result<(), RuntimeError> {
+        let env = Environment::new();
+
+        match run(&env, "printf('b\\na\\nc\\n') | sort() | head(lines: 2)").await? {
+            Value::Stream(stream) => assert_eq!(stream.merge().await, b"a\nb\n"),
+            value => panic!("Expected stream, got {}", value)
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    pub async fn test_stderr_pipeline() -> Result<(), RuntimeError> {
+        let env = Environment::new();
+
+        match run(&env, "sh('-c', 'echo out; echo err >&2') |e cat()").await? {
+            Value::Stream(stream) => assert_eq!(stream.merge().await, b"err\n"),
+            value => panic!("Expected stream, got {}", value)
+        }
+
+        match run(&env, "sh('-c', 'echo out; echo err >&2') |oe sort()").await? {
+            Value::Stream(stream) => assert_eq!(stream.merge().await, b"err\nout\n"),
+            value => panic!("Expected stream, got {}", value)
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    pub async fn test_builtin_stage() -> Result<(), RuntimeError> {
+        let env = Environment::new();
+
+        assert!(matches!(run(&env, "echo('four') | len").await, Err(RuntimeError::InvalidArgument(_, _))));
+        assert!(matches!(run(&env, "'four' | len").await?, Value::Integer(4)));
+        assert!(matches!(run(&env, "'four' | len()").await?, Value::Integer(4)));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    pub async fn test_arithmetic() -> Result<(), RuntimeError> {
+        let env = Environment::new();
+
+        assert!(matches!(run(&env, "1 + 2 * 3").await?, Value::Integer(7)));
+        assert!(matches!(run(&env, "(1 + 2) * 3").await?, Value::Integer(9)));
+        assert!(matches!(run(&env, "10 - 4 - 3").await?, Value::Integer(3)));
+        assert!(matches!(run(&env, "2 ^ 3 ^ 2").await?, Value::Integer(512)));
+        assert!(matches!(run(&env, "7 % 4 + 0.5").await?, Value::Float(f) if f == 3.5));
+        assert!(matches!(run(&env, "'esh' + ' ' + 'shell'").await?, Value::String(str) if str == "esh shell"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    pub async fn test_unary_operators() -> Result<(), RuntimeError> {
+        let env = Environment::new();
+
+        assert!(matches!(run(&env, "-2 ^ 2").await?, Value::Integer(-4)));
+        assert!(matches!(run(&env, "1-2").await?, Value::Integer(-1)));
+        assert!(matches!(run(&env, "3 - -2 * -1").await?, Value::Integer(1)));
+        assert!(matches!(run(&env, "!true || !(1 > 2)").await?, Value::Boolean(true)));
+        assert!(matches!(run(&env, "!'' == true").await?, Value::Boolean(true)));
+        assert!(matches!(run(&env, "1 +").await, Err(RuntimeError::Syntax(SyntaxError::UnexpectedEOF()))));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    pub async fn test_comparison_and_logic() -> Result<(), RuntimeError> {
+        let env = Environment::new();
+
+        assert!(matches!(run(&env, "1 + 1 == 2 && 3 > 2").await?, Value::Boolean(true)));
+        assert!(matches!(run(&env, "1 >= 2 || 'a' < 'b'").await?, Value::Boolean(true)));
+        assert!(matches!(run(&env, "false && esh_undefined_variable").await?, Value::Boolean(false)));
+        assert!(matches!(run(&env, "true || esh_undefined_variable").await?, Value::Boolean(true)));
+        assert!(matches!(run(&env, "true && esh_undefined_variable").await, Err(RuntimeError::NoValue(_))));
+        assert!(matches!(run(&env, "1 + 'a'").await, Err(RuntimeError::InvalidOperation(_))));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    pub async fn test_assignment() -> Result<(), RuntimeError> {
+        let env = Environment::new();
+
+        assert!(matches!(run(&env, "count = 'four' | len").await?, Value::Null));
+        assert!(matches!(run(&env, "count * 2").await?, Value::Integer(8)));
+        assert!(matches!(run(&env, "count = count + 1").await?, Value::Null));
+        assert!(matches!(run(&env, "count").await?, Value::Integer(5)));
+        assert!(matches!(run(&env, "a = 2").await?, Value::Null));
+        assert!(matches!(run(&env, "b = a").await?, Value::Null));
+        assert!(matches!(run(&env, "a + b").await?, Value::Integer(4)));
+        assert!(matches!(run(&env, "inc = x -> x + count").await?, Value::Null));
+        assert!(matches!(run(&env, "inc").await?, Value::Function(Function::Lambda(args, _, _, _)) if args == ["x"]));
+        assert!(matches!(run(&env, "1 = 2").await, Err(RuntimeError::InvalidOperation(_))));
+        assert!(matches!(run(&env, "esh_undefined_variable").await, Err(RuntimeError::NoValue(_))));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    pub async fn test_string_literals() -> Result<(), RuntimeError> {
+        let env = Environment::new();
+
+        run(&env, "name = 'esh'").await?;
+
+        assert_eq!(run(&env, r"'tab\there\u{21}'").await?.to_string(), "'tab\there!'");
+        assert_eq!(run(&env, r"b'\x00\xff'").await?.to_string(), r"b'\x00\xff'");
+        assert_eq!(run(&env, "f'{name} has {name | len} letters, {{ braces }}'").await?.to_string(), "'esh has 3 letters, { braces }'");
+        assert_eq!(run(&env, "f'{echo(name)}!'").await?.to_string(), "'esh!'");
+        assert_eq!(run(&env, "'''\n  it's \"quoted\"\n'''").await?.to_string(), "'  it\\'s \"quoted\"\n'");
+        assert_eq!(run(&env, r"r'^\d+$'").await?.to_string(), r"r'^\d+$'");
+        assert_eq!(run(&env, "{ id: 12, name: 'x' } - { id: r'^\\d+$' }").await?.to_string(), "{ name: 'x' }");
+        assert_eq!(run(&env, "{ id: 12, name: 'x' } - { name: r'y' }").await?.to_string(), "{ id: 12, name: 'x' }");
+
+        assert!(matches!(run(&env, r"r'(' + 1").await, Err(RuntimeError::Syntax(SyntaxError::InvalidString(..)))));
+        assert!(matches!(run(&env, "f'{1 +}'").await, Err(RuntimeError::Syntax(SyntaxError::InvalidString(..)))));
+        assert!(matches!(run(&env, "f'{undefined_name_in_esh}'").await, Err(RuntimeError::NoValue(_))));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    pub async fn test_dict_literals() -> Result<(), RuntimeError> {
+        let env = Environment::new();
+
+        assert_eq!(run(&env, "{ name: 'John Doe', 'content-type': 'text/plain', (1 + 1): true }").await?.to_string(), "{ name: 'John Doe', 'content-type': 'text/plain', '2': true }");
+        assert_eq!(run(&env, "{ 'Applications', 'config', 1 + 2, }").await?.to_string(), "{ 'Applications', 'config', 3 }");
+        assert_eq!(run(&env, "{ a: { b: 1 } } | keys").await?.to_string(), "{ 'a' }");
+        assert_eq!(run(&env, "{:}").await?.to_string(), "{:}");
+        assert!(matches!(run(&env, "{ a: 1, a: 2 }").await, Err(RuntimeError::DuplicateKey(key)) if key == "a"));
+        assert!(matches!(run(&env, "{ a: 1, 2 }").await, Err(RuntimeError::InvalidOperation(_))));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    pub async fn test_spread() -> Result<(), RuntimeError> {
+        let env = Environment::new();
+
+        run(&env, "defaults = { colour: true, limits: { depth: 2, width: 80 } }").await?;
+        run(&env, "items = { 2, 3 }").await?;
+
+        assert_eq!(run(&env, "{ ...defaults, limits: { depth: 3 } }").await?.to_string(), "{ colour: true, limits: { depth: 3, width: 80 } }");
+        assert_eq!(run(&env, "{ ...defaults, ...{ colour: false } }").await?.to_string(), "{ colour: false, limits: { depth: 2, width: 80 } }");
+        assert_eq!(run(&env, "{ 1, ...items, 4 }").await?.to_string(), "{ 1, 2, 3, 4 }");
+        assert_eq!(run(&env, "defaults + { limits: { width: 100 } }").await?.to_string(), "{ colour: true, limits: { depth: 2, width: 100 } }");
+        assert!(matches!(run(&env, "{ 1, ...defaults }").await, Err(RuntimeError::InvalidOperation(_))));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    pub async fn test_index() -> Result<(), RuntimeError> {
+        let env = Environment::new();
+
+        run(&env, "index = { 'a', { 'b', 'c', 'd', { hi: 'hello', 'content-type': 'text/plain' } } }").await?;
+        run(&env, "key = 'hi'").await?;
+
+        assert_eq!(run(&env, "index.(1 + 0).3.hi").await?.to_string(), "'hello'");
+        assert_eq!(run(&env, "index.1.3.(key)").await?.to_string(), "'hello'");
+        assert_eq!(run(&env, "index.1.3.'content-type'").await?.to_string(), "'text/plain'");
+        assert_eq!(run(&env, "index.1.3.i'Content-Type'").await?.to_string(), "'text/plain'");
+        assert_eq!(run(&env, "index.1.0").await?.to_string(), "'b'");
+        assert_eq!(run(&env, "index.-1.-2").await?.to_string(), "'d'");
+        assert_eq!(run(&env, "index.(0 - 2)").await?.to_string(), "'a'");
+        assert_eq!(run(&env, "index.1.3 | .hi").await?.to_string(), "'hello'");
+        assert_eq!(run(&env, "index | .1.3 | keys | .0").await?.to_string(), "'hi'");
+        assert_eq!(run(&env, "'esh'.-1").await?.to_string(), "'h'");
+        assert_eq!(run(&env, "index.0 + index.1.0").await?.to_string(), "'ab'");
+
+        assert!(matches!(run(&env, "index.1.3.missing").await, Err(RuntimeError::MissingKey(key)) if key == "missing"));
+        assert!(matches!(run(&env, "index.2").await, Err(RuntimeError::IndexOutOfRange(2, 2))));
+        assert!(matches!(run(&env, "index.-3").await, Err(RuntimeError::IndexOutOfRange(-3, 2))));
+        assert!(matches!(run(&env, "index.a").await, Err(RuntimeError::InvalidOperation(_))));
+        assert!(matches!(run(&env, ".a").await, Err(RuntimeError::InvalidOperation(_))));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    pub async fn test_control_flow() -> Result<(), RuntimeError> {
+        let env = Environment::new();
+
+        assert_eq!(run(&env, "if 1 > 2 { 'a' } else if 2 > 1 { 'b' } else { 'c' }").await?.to_string(), "'b'");
+        assert!(matches!(run(&env, "if false { 'a' }").await?, Value::Null));
+
+        run(&env, "sign = n -> if n < 0 { -1 } else { 1 }").await?;
+        assert_eq!(run(&env, "sign(-5) + sign(5) * 10").await?.to_string(), "9");
+
+        run(&env, "total = 0").await?;
+        run(&env, "for i in { 1, 2, 3 } { total = total + i }").await?;
+        run(&env, "for key in { a: 1, bc: 2 } { total = total + len(key) }").await?;
+        run(&env, "for line in printf('x\\ny\\nz') { total = total + 1 }").await?;
+        assert!(matches!(run(&env, "total").await?, Value::Integer(12)));
+        assert!(matches!(run(&env, "i").await, Err(RuntimeError::NoValue(_))));
+
+        run(&env, "function fib(n) {
+            if n < 2 {
+                return n
+            }
+
+            fib(n - 1) + fib(n - 2)
+        }").await?;
+        assert!(matches!(run(&env, "fib(10)").await?, Value::Integer(55)));
+        assert_eq!(run(&env, "{ 1, 2, 3 } | map(fib)").await?.to_string(), "{ 1, 1, 2 }");
+
+        run(&env, "function first_even(list) { for i in list { if i % 2 == 0 { return i } }; return }").await?;
+        assert!(matches!(run(&env, "first_even({ 1, 3, 4, 6 })").await?, Value::Integer(4)));
+        assert!(matches!(run(&env, "first_even({ 1, 3 })").await?, Value::Null));
+
+        run(&env, "function counter(start) { count = start; function step(by) { count = count + by; count }; return step }").await?;
+        run(&env, "step = counter(10)").await?;
+        run(&env, "step(1)").await?;
+        run(&env, "step(2)").await?;
+        assert!(matches!(run(&env, "step(3)").await?, Value::Integer(16)));
+
+        // A function bound in the scope it captured doesn't keep that scope alive
+        let scope = env.child();
+        run(&scope, "function twice(x) { x * 2 }").await?;
+        let weak = scope.downgrade();
+        drop(scope);
+        assert!(weak.upgrade().is_none());
+        assert!(matches!(run(&env, "return 1").await, Err(RuntimeError::Return(_))));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    pub async fn test_try_catch() -> Result<(), RuntimeError> {
+        let env = Environment::new();
+
+        run(&env, "function inner(x) { x.missing }").await?;
+        run(&env, "outer = x -> inner(x)").await?;
+
+        let err = run(&env, "outer({ a: 1 })").await.unwrap_err();
+        assert_eq!(err.kind(), "KeyError");
+        assert_eq!(err.trace(), ["inner", "<lambda>"]);
+        assert_eq!(err.to_string(), "KeyError: Key 'missing' does not exist\n  in inner\n  in <lambda>");
+
+        assert_eq!(run(&env, "try { outer({ a: 1 }) } catch err { err.kind + ': ' + err.trace.0 }").await?.to_string(), "'KeyError: inner'");
+        assert_eq!(run(&env, "try { 1 + 'a' } catch { 'caught' }").await?.to_string(), "'caught'");
+        run(&env, "x = try { 2 } catch { 3 }").await?;
+        assert!(matches!(run(&env, "x").await?, Value::Integer(2)));
+        assert_eq!(run(&env, "try { sh('-c', 'exit 3') } catch err { err.status }").await?.to_string(), "3");
+        assert_eq!(run(&env, "try { esh_undefined_variable } catch err { err.kind }").await?.to_string(), "'NameError'");
+
+        match run(&env, "try { echo('ok') } catch { 'failed' }").await? {
+            Value::Stream(stream) => assert_eq!(stream.merge().await, b"ok\n"),
+            value => panic!("Expected stream, got {}", value)
+        }
+
+        run(&env, "function first(list) { try { return list.0 } catch { return null } }").await?;
+        assert!(matches!(run(&env, "first({ 5 })").await?, Value::Integer(5)));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    pub async fn test_spawn_missing_binary() {
+        let env = Environment::new();
+
+        assert!(matches!(run(&env, "esh_no_such_binary()").await, Err(RuntimeError::NoValue(_))));
+    }
+}
+
+/// Checks the expression evaluator against an independent reference: a recursive-descent parser with one grammar rule per
+/// precedence level, evaluated over integers and booleans.
+#[cfg(test)]
+mod reference_test {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    #[derive(Debug, Clone)]
+    enum Tok {
+        Int(i64),
+        Bool(bool),
+        Op(&'static str),
+        Open,
+        Close,
+    }
+
+    #[derive(Debug, Clone)]
+    enum Term {
+        Int(i64),
+        Bool(bool),
+        Group(Vec<Tok>),
+    }
+
+    const ARITHMETIC: &[&str] = &["+", "-", "*", "%", "^"];
+    const LOGICAL: &[&str] = &["||", "&&", "==", "!=", "<", ">", "<=", ">="];
+    const ALL: &[&str] = &["||", "&&", "==", "!=", "<", ">", "<=", ">=", "+", "-", "*", "%", "^"];
+
+    fn operand(term: impl Strategy<Value=Term>, prefixes: &'static [&'static str]) -> impl Strategy<Value=Vec<Tok>> {
+        (prop::option::weighted(0.2, prop::sample::select(prefixes)), term).prop_map(|(prefix, term)| {
+            let mut tokens: Vec<Tok> = prefix.into_iter().map(Tok::Op).collect();
+
+            match term {
+                Term::Int(int) => tokens.push(Tok::Int(int)),
+                Term::Bool(bool) => tokens.push(Tok::Bool(bool)),
+                Term::Group(group) => {
+                    tokens.push(Tok::Open);
+                    tokens.extend(group);
+                    tokens.push(Tok::Close);
                 }
+            }
+
+            tokens
+        })
+    }
 
-                while let Some(token) = opstack.pop_front() {
-                    output.push_back(token);
+    fn expression(term: impl Strategy<Value=Term> + Clone, ops: &'static [&'static str], prefixes: &'static [&'static str]) -> impl Strategy<Value=Vec<Tok>> {
+        (operand(term.clone(), prefixes), prop::collection::vec((prop::sample::select(ops), operand(term, prefixes)), 0..6))
+            .prop_map(|(first, rest)| {
+                let mut tokens = first;
+
+                for (op, operand) in rest {
+                    tokens.push(Tok::Op(op));
+                    tokens.extend(operand);
                 }
 
-                if output.len() > 1 {
-                    while let Some(op) = output.pop_back() {
-                        if let OpOrExpr::Operator(op) = op {
-                            match op {
-                                OperatorType::Add => {}
-                                OperatorType::Subtract => {}
-                                _ => return Err(SyntaxError::UnsupportedExpression(*ast.clone()))
-                            }
-                        } else {
-                            return Err(SyntaxError::UnsupportedExpression(*ast.clone()));
-                        }
-                    }
-                } else {
-                    if let Some(OpOrExpr::Literal(val)) = output.pop_back() {
-                        return match val {
-                            LiteralToken::Symbol(name) => if options.resolve_names_to_executables {
-                                match locate_binary(&name) {
-                                    Some(binary) => Ok(ByteStream::from_string(&binary)),
-                                    None => Err(SyntaxError::NoValue(name))
-                                }
-                            } else {
-                                std::env::var(&name)
-                                    .map(|i| ByteStream::from_string(&i))
-                                    .map_err(|_| SyntaxError::NoValue(name))
-                            },
-                            LiteralToken::String(str) => Ok(ByteStream::from_string(&str)),
-                            LiteralToken::Boolean(bool) => Ok(ByteStream::from_string(if bool { "true" } else { "false" })),
-                            LiteralToken::Number(num) => Ok(ByteStream::from_string(&format!("{}", num))),
+                tokens
+            })
+    }
+
+    /// Random expressions over the given leaves and operators, with parenthesised groups nested up to three deep
+    fn tokens(leaf: impl Strategy<Value=Term> + 'static, ops: &'static [&'static str], prefixes: &'static [&'static str]) -> impl Strategy<Value=Vec<Tok>> {
+        let term = leaf.prop_recursive(3, 32, 6, move |inner| expression(inner, ops, prefixes).prop_map(Term::Group));
+
+        expression(term, ops, prefixes)
+    }
+
+    fn render(tokens: &[Tok]) -> String {
+        tokens.iter()
+            .map(|i| match i {
+                Tok::Int(int) => int.to_string(),
+                Tok::Bool(bool) => bool.to_string(),
+                Tok::Op(op) => op.to_string(),
+                Tok::Open => "(".to_owned(),
+                Tok::Close => ")".to_owned(),
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    enum Node {
+        Int(i64),
+        Bool(bool),
+        Unary(&'static str, Box<Node>),
+        Binary(&'static str, Box<Node>, Box<Node>),
+    }
+
+    struct Reference<'a> {
+        tokens: &'a [Tok],
+        pos: usize,
+    }
+
+    impl Reference<'_> {
+        fn peek_op(&self, ops: &[&str]) -> Option<&'static str> {
+            match self.tokens.get(self.pos) {
+                Some(Tok::Op(op)) if ops.contains(op) => Some(*op),
+                _ => None,
+            }
+        }
+
+        fn left(&mut self, ops: &[&str], next: fn(&mut Self) -> Node) -> Node {
+            let mut lhs = next(self);
+
+            while let Some(op) = self.peek_op(ops) {
+                self.pos += 1;
+                lhs = Node::Binary(op, Box::new(lhs), Box::new(next(self)));
+            }
+
+            lhs
+        }
+
+        fn or(&mut self) -> Node { self.left(&["||"], Self::and) }
+        fn and(&mut self) -> Node { self.left(&["&&"], Self::equality) }
+        fn equality(&mut self) -> Node { self.left(&["==", "!="], Self::comparison) }
+        fn comparison(&mut self) -> Node { self.left(&["<", ">", "<=", ">="], Self::additive) }
+        fn additive(&mut self) -> Node { self.left(&["+", "-"], Self::multiplicative) }
+        fn multiplicative(&mut self) -> Node { self.left(&["*", "%"], Self::negation) }
+
+        fn negation(&mut self) -> Node {
+            if self.peek_op(&["-"]).is_some() {
+                self.pos += 1;
+                return Node::Unary("-", Box::new(self.negation()));
+            }
+
+            self.power()
+        }
+
+        fn power(&mut self) -> Node {
+            let base = self.not();
+
+            if self.peek_op(&["^"]).is_some() {
+                self.pos += 1;
+                return Node::Binary("^", Box::new(base), Box::new(self.negation()));
+            }
+
+            base
+        }
+
+        fn not(&mut self) -> Node {
+            if self.peek_op(&["!"]).is_some() {
+                self.pos += 1;
+                return Node::Unary("!", Box::new(self.not()));
+            }
+
+            let token = self.tokens[self.pos].clone();
+            self.pos += 1;
+
+            match token {
+                Tok::Int(int) => Node::Int(int),
+                Tok::Bool(bool) => Node::Bool(bool),
+                Tok::Open => {
+                    let node = self.or();
+                    self.pos += 1;
+                    node
+                }
+                token => panic!("Unexpected {:?}", token),
+            }
+        }
+    }
+
+    #[derive(Debug, PartialEq)]
+    enum Outcome {
+        Int(i64),
+        Bool(bool),
+        Error,
+        /// The result would be a float, which the reference doesn't model
+        Unsupported,
+    }
+
+    fn evaluate(node: &Node) -> Outcome {
+        let truthy = |outcome: &Outcome| matches!(outcome, Outcome::Int(i) if *i != 0) || matches!(outcome, Outcome::Bool(true));
+
+        match node {
+            Node::Int(int) => Outcome::Int(*int),
+            Node::Bool(bool) => Outcome::Bool(*bool),
+            Node::Unary(op, operand) => match (*op, evaluate(operand)) {
+                (_, outcome @ (Outcome::Error | Outcome::Unsupported)) => outcome,
+                ("!", outcome) => Outcome::Bool(!truthy(&outcome)),
+                (_, Outcome::Int(int)) => int.checked_neg().map_or(Outcome::Error, Outcome::Int),
+                _ => Outcome::Error,
+            },
+            Node::Binary(op @ ("&&" | "||"), lhs, rhs) => {
+                let lhs = evaluate(lhs);
+
+                if matches!(lhs, Outcome::Error | Outcome::Unsupported) {
+                    return lhs;
+                }
+
+                if truthy(&lhs) == (*op == "||") {
+                    return Outcome::Bool(truthy(&lhs));
+                }
+
+                match evaluate(rhs) {
+                    outcome @ (Outcome::Error | Outcome::Unsupported) => outcome,
+                    rhs => Outcome::Bool(truthy(&rhs)),
+                }
+            }
+            Node::Binary(op, lhs, rhs) => {
+                let lhs = evaluate(lhs);
+                if matches!(lhs, Outcome::Error | Outcome::Unsupported) {
+                    return lhs;
+                }
+
+                let rhs = evaluate(rhs);
+                if matches!(rhs, Outcome::Error | Outcome::Unsupported) {
+                    return rhs;
+                }
+
+                match (*op, lhs, rhs) {
+                    ("==", lhs, rhs) => Outcome::Bool(lhs == rhs),
+                    ("!=", lhs, rhs) => Outcome::Bool(lhs != rhs),
+                    (op @ ("<" | ">" | "<=" | ">="), lhs, rhs) => {
+                        let ordering = match (lhs, rhs) {
+                            (Outcome::Int(a), Outcome::Int(b)) => a.cmp(&b),
+                            (Outcome::Bool(a), Outcome::Bool(b)) => a.cmp(&b),
+                            _ => return Outcome::Error,
                         };
-                    }
 
-                    return Err(SyntaxError::UnsupportedExpression(*ast.clone()));
+                        Outcome::Bool(match op {
+                            "<" => ordering.is_lt(),
+                            ">" => ordering.is_gt(),
+                            "<=" => ordering.is_le(),
+                            _ => ordering.is_ge(),
+                        })
+                    }
+                    (op, Outcome::Int(a), Outcome::Int(b)) => match op {
+                        "+" => a.checked_add(b).map_or(Outcome::Error, Outcome::Int),
+                        "-" => a.checked_sub(b).map_or(Outcome::Error, Outcome::Int),
+                        "*" => a.checked_mul(b).map_or(Outcome::Error, Outcome::Int),
+                        "%" => a.checked_rem(b).map_or(Outcome::Error, Outcome::Int),
+                        _ if b < 0 => Outcome::Unsupported,
+                        _ => u32::try_from(b).ok().and_then(|b| a.checked_pow(b)).map_or(Outcome::Error, Outcome::Int),
+                    },
+                    _ => Outcome::Error,
                 }
             }
-            _ => todo!()
+        }
+    }
+
+    fn check(tokens: Vec<Tok>) -> Result<(), TestCaseError> {
+        let expected = evaluate(&Reference { tokens: &tokens, pos: 0 }.or());
+        prop_assume!(expected != Outcome::Unsupported);
+
+        let source = render(&tokens);
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        let actual = runtime.block_on(run(&Environment::new(), &source));
+
+        let actual = match actual {
+            Ok(Value::Integer(int)) => Outcome::Int(int),
+            Ok(Value::Boolean(bool)) => Outcome::Bool(bool),
+            Ok(value) => panic!("{} evaluated to unexpected {}", source, value),
+            Err(_) => Outcome::Error,
         };
 
-        Err(SyntaxError::UnsupportedExpression(*ast.clone()))
-        // ByteStream {}
-    })
-}
+        prop_assert_eq!(actual, expected, "{}", source);
+        Ok(())
+    }
 
-pub fn locate_binary(hint: &str) -> Option<String> {
-    let path = std::env::var("PATH").unwrap_or_else(|_| "/usr/local/bin:/usr/bin:/bin".to_string());
-    let paths = path.split(':');
+    proptest! {
+        #[test]
+        fn test_arithmetic_matches_reference(tokens in tokens((0i64..6).prop_map(Term::Int), ARITHMETIC, &["-"])) {
+            check(tokens)?;
+        }
 
-    for path in paths {
-        let path = format!("{}/{}", path, hint);
-        if std::path::Path::new(&path).exists() {
-            return Some(path);
+        #[test]
+        fn test_logic_matches_reference(tokens in tokens(any::<bool>().prop_map(Term::Bool), LOGICAL, &["!"])) {
+            check(tokens)?;
         }
-    }
 
-    None
+        #[test]
+        fn test_mixed_matches_reference(tokens in tokens(prop_oneof![(0i64..10).prop_map(Term::Int), any::<bool>().prop_map(Term::Bool)], ALL, &["!", "-"])) {
+            check(tokens)?;
+        }
+    }
 }
@@ -0,0 +1,89 @@
+use std::fs;
+use std::io::{self, Write};
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::time::{Duration, SystemTime};
+
+use nix::unistd::{access, AccessFlags, Gid, Group, Uid, User};
+
+use crate::command::fs::{FileSystem, Metadata};
+use crate::command::stream::ByteStream;
+
+/// The `file:` scheme, which is the machine's own filesystem
+pub struct LocalFileSystem;
+
+impl FileSystem for LocalFileSystem {
+    fn metadata(&self, path: &str, follow: bool) -> io::Result<Metadata> {
+        let metadata = fs::symlink_metadata(path)?;
+        let symlink = metadata.file_type().is_symlink();
+
+        // A broken link still has metadata of its own
+        let metadata = match follow && symlink {
+            true => fs::metadata(path).unwrap_or(metadata),
+            false => metadata,
+        };
+
+        Ok(Metadata {
+            directory: metadata.is_dir(),
+            file: metadata.is_file(),
+            symlink,
+            size: metadata.len(),
+            modified: metadata.modified().ok(),
+            changed: u64::try_from(metadata.ctime()).ok().map(|secs| SystemTime::UNIX_EPOCH + Duration::new(secs, metadata.ctime_nsec() as u32)),
+            accessed: metadata.accessed().ok(),
+            permissions: metadata.permissions().mode() & 0o7777,
+            owner: User::from_uid(Uid::from_raw(metadata.uid())).ok().flatten().map(|user| user.name),
+            group: Group::from_gid(Gid::from_raw(metadata.gid())).ok().flatten().map(|group| group.name),
+            readable: access(path, AccessFlags::R_OK).is_ok(),
+            writable: access(path, AccessFlags::W_OK).is_ok(),
+            executable: access(path, AccessFlags::X_OK).is_ok(),
+            id: Some((metadata.dev(), metadata.ino())),
+        })
+    }
+
+    fn read_dir(&self, path: &str) -> io::Result<Vec<String>> {
+        fs::read_dir(path)?
+            .map(|entry| entry.map(|entry| entry.file_name().to_string_lossy().into_owned()))
+            .collect()
+    }
+
+    fn read(&self, path: &str) -> io::Result<Vec<u8>> {
+        fs::read(path)
+    }
+
+    fn open(&self, path: &str) -> io::Result<ByteStream> {
+        let file = fs::File::open(path)?;
+
+        // Directories can be opened, but not read from
+        if file.metadata()?.is_dir() {
+            return Err(io::ErrorKind::IsADirectory.into());
+        }
+
+        Ok(ByteStream::from_reader(tokio::fs::File::from_std(file)))
+    }
+
+    fn write(&self, path: &str, contents: &[u8], append: bool) -> io::Result<()> {
+        fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(append)
+            .truncate(!append)
+            .open(path)?
+            .write_all(contents)
+    }
+
+    fn create_dir(&self, path: &str) -> io::Result<()> {
+        fs::create_dir(path)
+    }
+
+    fn remove(&self, path: &str) -> io::Result<()> {
+        // Links are removed themselves rather than what they point to
+        match fs::symlink_metadata(path)?.is_dir() {
+            true => fs::remove_dir(path),
+            false => fs::remove_file(path),
+        }
+    }
+
+    fn rename(&self, from: &str, to: &str) -> io::Result<()> {
+        fs::rename(from, to)
+    }
+}
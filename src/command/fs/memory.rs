@@ -0,0 +1,227 @@
+use std::collections::BTreeMap;
+use std::io::{self, ErrorKind};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use crate::command::fs::{FileSystem, Metadata};
+
+/// The `mem:` scheme, a filesystem which lives only as long as the shell does. It's a scratch space for scripts, and
+/// a way to test builtins without touching the disk.
+#[derive(Default)]
+pub struct MemoryFileSystem {
+    /// Every file and directory but the root, by path. Sorting by path keeps the contents of a directory together.
+    entries: Mutex<BTreeMap<String, Entry>>,
+}
+
+struct Entry {
+    /// A file's contents, or `None` for a directory
+    contents: Option<Vec<u8>>,
+    modified: SystemTime,
+    accessed: SystemTime,
+}
+
+impl Entry {
+    fn new(contents: Option<Vec<u8>>) -> Entry {
+        let now = SystemTime::now();
+        Entry { contents, modified: now, accessed: now }
+    }
+}
+
+/// The paths of the entries directly or indirectly inside `path`
+fn descendants<'a>(entries: &'a BTreeMap<String, Entry>, path: &str) -> impl Iterator<Item=&'a String> {
+    let prefix = if path == "/" { "/".to_owned() } else { format!("{}/", path) };
+    entries.range(prefix.clone()..).map(|(path, _)| path).take_while(move |path| path.starts_with(&prefix))
+}
+
+fn parent(path: &str) -> &str {
+    match path.rfind('/') {
+        Some(0) | None => "/",
+        Some(end) => &path[..end],
+    }
+}
+
+impl MemoryFileSystem {
+    /// Fails unless `path` is the root or an existing directory
+    fn check_dir(entries: &BTreeMap<String, Entry>, path: &str) -> io::Result<()> {
+        match entries.get(path) {
+            _ if path == "/" => Ok(()),
+            Some(Entry { contents: None, .. }) => Ok(()),
+            Some(_) => Err(ErrorKind::NotADirectory.into()),
+            None => Err(ErrorKind::NotFound.into()),
+        }
+    }
+}
+
+impl FileSystem for MemoryFileSystem {
+    /// Entries belong to nobody in particular, and anyone may read and write them
+    fn metadata(&self, path: &str, _: bool) -> io::Result<Metadata> {
+        let entries = self.entries.lock().unwrap();
+
+        let Some(entry) = entries.get(path) else {
+            return match path {
+                "/" => Ok(Metadata { directory: true, permissions: 0o755, readable: true, writable: true, executable: true, ..Metadata::default() }),
+                _ => Err(ErrorKind::NotFound.into()),
+            };
+        };
+
+        Ok(Metadata {
+            directory: entry.contents.is_none(),
+            file: entry.contents.is_some(),
+            symlink: false,
+            size: entry.contents.as_ref().map_or(0, |contents| contents.len() as u64),
+            modified: Some(entry.modified),
+            changed: Some(entry.modified),
+            accessed: Some(entry.accessed),
+            permissions: if entry.contents.is_some() { 0o644 } else { 0o755 },
+            owner: None,
+            group: None,
+            readable: true,
+            writable: true,
+            executable: entry.contents.is_none(),
+            id: None,
+        })
+    }
+
+    fn read_dir(&self, path: &str) -> io::Result<Vec<String>> {
+        let entries = self.entries.lock().unwrap();
+        Self::check_dir(&entries, path)?;
+
+        Ok(descendants(&entries, path)
+            .filter(|child| parent(child) == path)
+            .map(|child| child[child.rfind('/').unwrap() + 1..].to_owned())
+            .collect())
+    }
+
+    fn read(&self, path: &str) -> io::Result<Vec<u8>> {
+        let mut entries = self.entries.lock().unwrap();
+
+        match entries.get_mut(path) {
+            Some(Entry { contents: Some(contents), accessed, .. }) => {
+                *accessed = SystemTime::now();
+                Ok(contents.clone())
+            }
+            Some(_) => Err(ErrorKind::IsADirectory.into()),
+            None if path == "/" => Err(ErrorKind::IsADirectory.into()),
+            None => Err(ErrorKind::NotFound.into()),
+        }
+    }
+
+    fn write(&self, path: &str, contents: &[u8], append: bool) -> io::Result<()> {
+        let mut entries = self.entries.lock().unwrap();
+        Self::check_dir(&entries, parent(path))?;
+
+        match entries.get_mut(path) {
+            Some(Entry { contents: Some(existing), modified, .. }) => {
+                if !append {
+                    existing.clear();
+                }
+
+                existing.extend_from_slice(contents);
+                *modified = SystemTime::now();
+            }
+            Some(_) => return Err(ErrorKind::IsADirectory.into()),
+            None if path == "/" => return Err(ErrorKind::IsADirectory.into()),
+            None => {
+                entries.insert(path.to_owned(), Entry::new(Some(contents.to_vec())));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn create_dir(&self, path: &str) -> io::Result<()> {
+        let mut entries = self.entries.lock().unwrap();
+        Self::check_dir(&entries, parent(path))?;
+
+        if path == "/" || entries.contains_key(path) {
+            return Err(ErrorKind::AlreadyExists.into());
+        }
+
+        entries.insert(path.to_owned(), Entry::new(None));
+        Ok(())
+    }
+
+    fn remove(&self, path: &str) -> io::Result<()> {
+        let mut entries = self.entries.lock().unwrap();
+
+        if path == "/" {
+            return Err(ErrorKind::PermissionDenied.into());
+        } else if !entries.contains_key(path) {
+            return Err(ErrorKind::NotFound.into());
+        } else if descendants(&entries, path).next().is_some() {
+            return Err(ErrorKind::DirectoryNotEmpty.into());
+        }
+
+        entries.remove(path);
+        Ok(())
+    }
+
+    fn rename(&self, from: &str, to: &str) -> io::Result<()> {
+        let mut entries = self.entries.lock().unwrap();
+        Self::check_dir(&entries, parent(to))?;
+
+        let Some(entry) = entries.get(from) else {
+            return Err(if from == "/" { ErrorKind::PermissionDenied } else { ErrorKind::NotFound }.into());
+        };
+
+        if from == to {
+            return Ok(());
+        } else if to.starts_with(&format!("{}/", from)) {
+            return Err(io::Error::new(ErrorKind::InvalidInput, "cannot move a directory into itself"));
+        }
+
+        // A file may replace another file, as on disk, but nothing may replace a directory
+        match (entry.contents.is_some(), entries.get(to)) {
+            (_, None) => {}
+            (true, Some(Entry { contents: Some(_), .. })) => {}
+            (_, Some(Entry { contents: None, .. })) => return Err(ErrorKind::IsADirectory.into()),
+            (false, Some(_)) => return Err(ErrorKind::NotADirectory.into()),
+        }
+
+        let moved: Vec<String> = descendants(&entries, from).cloned().chain([from.to_owned()]).collect();
+
+        for path in moved {
+            let entry = entries.remove(&path).unwrap();
+            entries.insert(format!("{}{}", to, &path[from.len()..]), entry);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn test_memory_filesystem() -> io::Result<()> {
+        let fs = MemoryFileSystem::default();
+
+        fs.create_dir("/notes")?;
+        fs.create_dir("/notes/old")?;
+        fs.write("/notes/todo.txt", b"milk", false)?;
+        fs.write("/notes/todo.txt", b", eggs", true)?;
+        fs.write("/notes-2", b"", false)?;
+
+        assert_eq!(fs.read("/notes/todo.txt")?, b"milk, eggs");
+        assert_eq!(fs.read_dir("/notes")?, ["old", "todo.txt"]);
+        assert_eq!(fs.read_dir("/")?, ["notes", "notes-2"]);
+        assert!(fs.metadata("/notes", true)?.directory);
+        assert_eq!(fs.metadata("/notes/todo.txt", true)?.size, 10);
+
+        assert_eq!(fs.write("/missing/file", b"", false).unwrap_err().kind(), ErrorKind::NotFound);
+        assert_eq!(fs.write("/notes-2/file", b"", false).unwrap_err().kind(), ErrorKind::NotADirectory);
+        assert_eq!(fs.read("/notes").unwrap_err().kind(), ErrorKind::IsADirectory);
+        assert_eq!(fs.remove("/notes").unwrap_err().kind(), ErrorKind::DirectoryNotEmpty);
+        assert_eq!(fs.rename("/notes", "/notes/old/notes").unwrap_err().kind(), ErrorKind::InvalidInput);
+
+        fs.rename("/notes", "/archive")?;
+        assert_eq!(fs.read_dir("/archive")?, ["old", "todo.txt"]);
+        assert_eq!(fs.read_dir("/")?, ["archive", "notes-2"]);
+
+        fs.remove("/archive/old")?;
+        assert_eq!(fs.read_dir("/archive")?, ["todo.txt"]);
+
+        Ok(())
+    }
+}
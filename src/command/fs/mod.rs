@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+use std::io;
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
+
+use lazy_static::lazy_static;
+
+use crate::command::location::Location;
+use crate::command::runtime_err::RuntimeError;
+use crate::command::stream::ByteStream;
+
+mod local;
+mod memory;
+
+pub use local::LocalFileSystem;
+pub use memory::MemoryFileSystem;
+
+/// What a provider knows about a file or directory. Providers fill in the times they keep track of and leave the rest
+/// as `None`.
+#[derive(Debug, Clone, Default)]
+pub struct Metadata {
+    pub directory: bool,
+    pub file: bool,
+    pub symlink: bool,
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+    pub changed: Option<SystemTime>,
+    pub accessed: Option<SystemTime>,
+    /// The Unix permission bits, such as `0o644`
+    pub permissions: u32,
+    pub owner: Option<String>,
+    pub group: Option<String>,
+    /// Whether the shell's user may read, write or execute the location
+    pub readable: bool,
+    pub writable: bool,
+    pub executable: bool,
+    /// Tells apart entries which are the same even when reached by different paths, such as through a link
+    pub id: Option<(u64, u64)>,
+}
+
+/// A filesystem which holds the locations of one scheme. Paths passed to a provider are absolute and normalised, as
+/// `Location` keeps them.
+pub trait FileSystem: Send + Sync {
+    /// Describes a location. If it's a symbolic link and `follow` is set, the rest is taken from what it links to.
+    fn metadata(&self, path: &str, follow: bool) -> io::Result<Metadata>;
+
+    /// The names of the entries in a directory
+    fn read_dir(&self, path: &str) -> io::Result<Vec<String>>;
+
+    fn read(&self, path: &str) -> io::Result<Vec<u8>>;
+
+    /// Opens a file to be read a chunk at a time as the stream is consumed. Providers which can't do better read it all
+    /// at once.
+    fn open(&self, path: &str) -> io::Result<ByteStream> {
+        self.read(path).map(ByteStream::from_bytes)
+    }
+
+    /// Replaces the contents of a file, or adds to the end of them if `append` is set. The file is created if it doesn't
+    /// exist, but the directory it's in must.
+    fn write(&self, path: &str, contents: &[u8], append: bool) -> io::Result<()>;
+
+    fn create_dir(&self, path: &str) -> io::Result<()>;
+
+    /// Removes a file, or a directory if it's empty
+    fn remove(&self, path: &str) -> io::Result<()>;
+
+    /// Moves a file or directory within the filesystem
+    fn rename(&self, from: &str, to: &str) -> io::Result<()>;
+}
+
+lazy_static! {
+    static ref PROVIDERS: RwLock<HashMap<String, Arc<dyn FileSystem>>> = RwLock::new(HashMap::from([
+        ("file".to_owned(), Arc::new(LocalFileSystem) as Arc<dyn FileSystem>),
+        ("mem".to_owned(), Arc::new(MemoryFileSystem::default()) as Arc<dyn FileSystem>),
+    ]));
+}
+
+/// Makes the locations of a scheme available through `provider`, replacing any provider already registered for it.
+pub fn register(scheme: &str, provider: Arc<dyn FileSystem>) {
+    PROVIDERS.write().unwrap().insert(scheme.to_ascii_lowercase(), provider);
+}
+
+pub fn provider(scheme: &str) -> Option<Arc<dyn FileSystem>> {
+    PROVIDERS.read().unwrap().get(scheme).cloned()
+}
+
+/// Runs `operation` on the path of `location` with the provider registered for its scheme. Errors name the location
+/// they happened at.
+pub fn with<T>(location: &Location, operation: impl FnOnce(&dyn FileSystem, &str) -> io::Result<T>) -> Result<T, RuntimeError> {
+    let provider = provider(location.scheme())
+        .ok_or_else(|| RuntimeError::FileError(location.to_string(), io::ErrorKind::Unsupported, format!("no filesystem is registered for '{}:'", location.scheme())))?;
+
+    operation(provider.as_ref(), location.path()).map_err(|err| RuntimeError::FileError(location.to_string(), err.kind(), err.to_string()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn test_providers_by_scheme() -> Result<(), RuntimeError> {
+        register("scratch", Arc::new(MemoryFileSystem::default()));
+
+        let location = Location::parse("scratch:/greeting");
+        with(&location, |fs, path| fs.write(path, b"hello", false))?;
+        assert_eq!(with(&location, |fs, path| fs.read(path))?, b"hello");
+        assert!(with(&Location::parse("mem:/greeting"), |fs, path| fs.read(path)).is_err());
+
+        let err = with(&Location::parse("nowhere:/x"), |fs, path| fs.read(path)).unwrap_err();
+        assert_eq!(err.message(), "nowhere:/x: no filesystem is registered for 'nowhere:'");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,130 @@
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+
+use crate::command::value::Value;
+
+/// Where a file or directory lives, written `scheme:/path`. The scheme picks the filesystem provider which looks after
+/// it, such as `file:` for the local filesystem or `mem:` for one held in memory. Paths are always absolute, with `.` and
+/// `..` resolved.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Location {
+    scheme: String,
+    path: String,
+}
+
+impl Location {
+    /// The location of `path` in the given scheme. Relative paths are taken from the root.
+    pub fn new(scheme: &str, path: &str) -> Location {
+        Location { scheme: scheme.to_ascii_lowercase(), path: normalise("/", path) }
+    }
+
+    /// A location on the local filesystem. Relative paths are taken from the current directory.
+    pub fn local(path: impl AsRef<Path>) -> Location {
+        let path = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("/")).join(path);
+        Location::new("file", &path.to_string_lossy())
+    }
+
+    /// Reads a location as it's printed. Text without a scheme is a path on the local filesystem, so `config/esh.toml`
+    /// and `file:config/esh.toml` are the same place.
+    pub fn parse(str: &str) -> Location {
+        match str.split_once(':') {
+            Some((scheme, path)) if is_scheme(scheme) && scheme.eq_ignore_ascii_case("file") => Location::local(path),
+            Some((scheme, path)) if is_scheme(scheme) => Location::new(scheme, path),
+            _ => Location::local(str),
+        }
+    }
+
+    /// The location a string or location value refers to
+    pub fn from_value(value: &Value) -> Option<Location> {
+        match value {
+            Value::Location(location) => Some(location.clone()),
+            Value::String(str) => Some(Location::parse(str)),
+            _ => None,
+        }
+    }
+
+    pub fn scheme(&self) -> &str {
+        &self.scheme
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// The last part of the path, which is empty for the root
+    pub fn name(&self) -> &str {
+        &self.path[self.path.rfind('/').map_or(0, |i| i + 1)..]
+    }
+
+    /// The directory containing the location, or `None` for the root
+    pub fn parent(&self) -> Option<Location> {
+        match self.path.rfind('/') {
+            _ if self.path == "/" => None,
+            Some(0) => Some(Location { path: "/".to_owned(), ..self.clone() }),
+            Some(end) => Some(Location { path: self.path[..end].to_owned(), ..self.clone() }),
+            None => None,
+        }
+    }
+
+    /// The location `path` refers to from this one, which stays within the same scheme
+    pub fn join(&self, path: &str) -> Location {
+        Location { scheme: self.scheme.clone(), path: normalise(&self.path, path) }
+    }
+
+    /// The path on the local filesystem, if the location is on it
+    pub fn local_path(&self) -> Option<PathBuf> {
+        (self.scheme == "file").then(|| PathBuf::from(&self.path))
+    }
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.scheme, self.path)
+    }
+}
+
+/// Schemes are a letter followed by letters, digits, `+`, `-` or `.`, as in URLs
+fn is_scheme(scheme: &str) -> bool {
+    scheme.starts_with(|c: char| c.is_ascii_alphabetic()) && scheme.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+}
+
+/// Resolves `path` against `base`, dropping empty and `.` parts and going up a level for each `..`
+fn normalise(base: &str, path: &str) -> String {
+    let mut parts: Vec<&str> = if path.starts_with('/') { vec![] } else { base.split('/').filter(|part| !part.is_empty()).collect() };
+
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+
+    format!("/{}", parts.join("/"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn test_parse_location() {
+        let location = Location::parse("mem:/tmp//notes/../todo.txt");
+        assert_eq!(location.to_string(), "mem:/tmp/todo.txt");
+        assert_eq!(location.name(), "todo.txt");
+        assert_eq!(location.parent().map(|parent| parent.to_string()).as_deref(), Some("mem:/tmp"));
+        assert_eq!(location.join("../a/./b").to_string(), "mem:/tmp/a/b");
+        assert_eq!(location.join("/etc").to_string(), "mem:/etc");
+        assert_eq!(Location::parse("MEM:x").to_string(), "mem:/x");
+        assert_eq!(Location::parse("mem:/").parent(), None);
+
+        let cwd = std::env::current_dir().unwrap();
+        assert_eq!(Location::parse("file:/home/user").local_path(), Some(PathBuf::from("/home/user")));
+        assert_eq!(Location::parse("Cargo.toml").local_path(), Some(cwd.join("Cargo.toml")));
+        assert_eq!(Location::parse("file:src/..").local_path(), Some(cwd.clone()));
+        assert_eq!(Location::parse("./a:b").local_path(), Some(cwd.join("a:b")));
+        assert_eq!(Location::parse("mem:/x").local_path(), None);
+    }
+}
@@ -1,3 +1,12 @@
 pub mod parser;
+pub mod builtins;
 pub mod eval;
+pub mod fs;
+pub mod location;
+pub mod module;
+pub mod ops;
 pub mod proc;
+pub mod runtime_err;
+pub mod scope;
+pub mod stream;
+pub mod value;
@@ -0,0 +1,186 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::command::eval::eval;
+use crate::command::parser::{parse_statements, tokenise, ASTNode};
+use crate::command::runtime_err::RuntimeError;
+use crate::command::scope::Environment;
+use crate::command::value::Dict;
+
+struct Modules {
+    search_path: Vec<PathBuf>,
+    /// The bindings defined by each module which has finished loading, by canonical path
+    cache: HashMap<PathBuf, Dict>,
+    /// The modules currently being loaded, innermost last
+    loading: Vec<PathBuf>,
+}
+
+thread_local! {
+    static MODULES: RefCell<Modules> = RefCell::new(Modules {
+        search_path: default_search_path(),
+        cache: HashMap::new(),
+        loading: Vec::new(),
+    });
+}
+
+/// The directories listed in `ESH_PATH`, or the current directory if it isn't set.
+fn default_search_path() -> Vec<PathBuf> {
+    match std::env::var("ESH_PATH") {
+        Ok(path) => std::env::split_paths(&path).collect(),
+        Err(_) => vec![PathBuf::from(".")]
+    }
+}
+
+pub fn search_path() -> Vec<PathBuf> {
+    MODULES.with(|modules| modules.borrow().search_path.clone())
+}
+
+pub fn set_search_path(search_path: Vec<PathBuf>) {
+    MODULES.with(|modules| modules.borrow_mut().search_path = search_path);
+}
+
+/// Finds the file a module name refers to. Names starting with `./` or `../` are relative to the directory of the module
+/// doing the importing, or the current directory at the prompt. Other relative names are looked up in each directory of
+/// the search path in turn. The `.esh` extension may be left off.
+fn resolve(module: &str) -> Option<PathBuf> {
+    let candidates = |dir: &Path| [dir.join(module), dir.join(format!("{}.esh", module))];
+
+    let dirs = if Path::new(module).is_absolute() {
+        vec![PathBuf::new()]
+    } else if module.starts_with("./") || module.starts_with("../") {
+        vec![MODULES.with(|modules| modules.borrow().loading.last().and_then(|i| i.parent()).map(Path::to_owned))
+            .unwrap_or_else(|| PathBuf::from("."))]
+    } else {
+        search_path()
+    };
+
+    dirs.iter()
+        .flat_map(|dir| candidates(dir))
+        .find(|path| path.is_file())
+        .and_then(|path| path.canonicalize().ok())
+}
+
+/// Loads a module and returns the bindings it makes at its top level, leaving out the names it imports itself. Each
+/// module is only evaluated once, after which its bindings are served from a cache.
+pub async fn import(module: &str) -> Result<Dict, RuntimeError> {
+    let path = resolve(module)
+        .ok_or_else(|| RuntimeError::ImportError(module.to_owned(), "no such module".to_owned()))?;
+
+    if let Some(bindings) = MODULES.with(|modules| modules.borrow().cache.get(&path).cloned()) {
+        return Ok(bindings);
+    }
+
+    MODULES.with(|modules| {
+        let mut modules = modules.borrow_mut();
+
+        match modules.loading.iter().position(|i| i == &path) {
+            Some(start) => Err(RuntimeError::ImportCycle(modules.loading[start..].iter()
+                .chain([&path])
+                .map(|i| i.display().to_string())
+                .collect())),
+            None => {
+                modules.loading.push(path.clone());
+                Ok(())
+            }
+        }
+    })?;
+
+    let bindings = load(&path).await;
+
+    MODULES.with(|modules| {
+        let mut modules = modules.borrow_mut();
+        modules.loading.pop();
+
+        if let Ok(bindings) = &bindings {
+            modules.cache.insert(path, bindings.clone());
+        }
+    });
+
+    bindings.map_err(|err| match err {
+        err @ (RuntimeError::ImportCycle(_) | RuntimeError::ImportError(..) | RuntimeError::InModule(..)) => err,
+        err => RuntimeError::ImportError(module.to_owned(), err.to_string())
+    })
+}
+
+async fn load(path: &Path) -> Result<Dict, RuntimeError> {
+    let source = std::fs::read_to_string(path)
+        .map_err(|err| RuntimeError::ImportError(path.display().to_string(), err.to_string()))?;
+
+    // Names the module imports are bound in a scope of their own around it, so that they're visible to it but aren't
+    // passed on to whatever imports it in turn
+    let imports = Environment::new();
+    let env = imports.child();
+
+    let statements = tokenise(&source).and_then(|tokens| parse_statements(&tokens))
+        .map_err(|err| RuntimeError::InModule(path.display().to_string(), source.clone(), Box::new(err.into())))?;
+
+    for statement in statements {
+        let scope = match statement.as_ref() {
+            ASTNode::Import(..) => imports.clone(),
+            _ => env.clone(),
+        };
+
+        eval(statement, scope, Default::default()).await?;
+    }
+
+    let mut bindings = env.bindings();
+    bindings.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    Ok(bindings.into_iter().collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::command::eval::run;
+    use crate::command::parser::render;
+    use crate::command::value::Value;
+
+    fn write_module(dir: &Path, name: &str, source: &str) {
+        std::fs::write(dir.join(name), source).unwrap();
+    }
+
+    #[tokio::test]
+    pub async fn test_import() -> Result<(), RuntimeError> {
+        let dir = std::env::temp_dir().join(format!("esh-test-import-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("lib")).unwrap();
+
+        write_module(&dir, "lib/maths.esh", "
+            import { offset } from './constants.esh'
+
+            function double(n) { n * 2 + offset }
+            square = n -> n * n
+        ");
+        write_module(&dir, "lib/constants.esh", "offset = 0");
+        write_module(&dir, "lib/a.esh", "import 'b'");
+        write_module(&dir, "lib/b.esh", "import 'a'");
+        write_module(&dir, "lib/broken.esh", "x = 1\ny = (x +)\n");
+
+        set_search_path(vec![dir.join("lib")]);
+
+        let env = Environment::new();
+        run(&env, "import { double, square } from 'maths'").await?;
+        assert!(matches!(run(&env, "double(square(3))").await?, Value::Integer(18)));
+        assert!(run(&env, "offset").await.is_err());
+
+        // Only what a module defines itself is imported, and not what it imports in turn
+        run(&env, "import 'maths.esh'").await?;
+        assert!(matches!(run(&env, "double").await?, Value::Function(_)));
+        assert!(matches!(run(&env, "offset").await, Err(RuntimeError::NoValue(_))));
+        assert_eq!(MODULES.with(|modules| modules.borrow().cache.len()), 2);
+
+        assert!(matches!(run(&env, "import { cube } from 'maths'").await, Err(RuntimeError::ImportError(..))));
+        assert!(matches!(run(&env, "import 'esh_no_such_module'").await, Err(RuntimeError::ImportError(..))));
+        assert!(matches!(run(&env, "import 'a'").await, Err(RuntimeError::ImportCycle(chain)) if chain.len() == 3));
+
+        // Mistakes in a module are shown in the module's own source
+        let err = run(&env, "import 'broken'").await.unwrap_err();
+        assert!(matches!(err, RuntimeError::InModule(_, _, ref err) if matches!(**err, RuntimeError::Syntax(_))));
+        assert!(render(&err, "import 'broken'", "<prompt>").contains("broken.esh:2:9\n  |\n2 | y = (x +)\n  |         ^"));
+
+        std::fs::remove_dir_all(dir).unwrap();
+
+        Ok(())
+    }
+}
@@ -0,0 +1,297 @@
+use std::cmp::Ordering;
+
+use crate::command::parser::OperatorType;
+use crate::command::runtime_err::RuntimeError;
+use crate::command::value::{Dict, Value};
+
+impl Value {
+    /// Whether the value counts as true in a condition. Null, false, zero and empty collections are falsy.
+    pub fn truthy(&self) -> bool {
+        match self {
+            Value::Null => false,
+            Value::Boolean(bool) => *bool,
+            Value::Integer(int) => *int != 0,
+            Value::Float(float) => *float != 0.0,
+            Value::String(str) => !str.is_empty(),
+            Value::Bytes(bytes) => !bytes.is_empty(),
+            Value::List(list) => !list.is_empty(),
+            Value::Dict(dict) => !dict.is_empty(),
+            _ => true,
+        }
+    }
+}
+
+/// Structural equality. Integers and floats compare by numeric value.
+pub fn equals(lhs: &Value, rhs: &Value) -> bool {
+    match (lhs, rhs) {
+        (Value::Null, Value::Null) => true,
+        (Value::Boolean(a), Value::Boolean(b)) => a == b,
+        (Value::Integer(a), Value::Integer(b)) => a == b,
+        (Value::Integer(a), Value::Float(b)) | (Value::Float(b), Value::Integer(a)) => *a as f64 == *b,
+        (Value::Float(a), Value::Float(b)) => a == b,
+        (Value::String(a), Value::String(b)) => a == b,
+        (Value::Location(a), Value::Location(b)) => a == b,
+        (Value::Bytes(a), Value::Bytes(b)) => a == b,
+        (Value::Date(a), Value::Date(b)) => a == b,
+        (Value::Regex(a), Value::Regex(b)) => a.as_str() == b.as_str(),
+        (Value::List(a), Value::List(b)) => a.len() == b.len() && a.iter().zip(b).all(|(a, b)| equals(a, b)),
+        (Value::Dict(a), Value::Dict(b)) => a.len() == b.len() && a.iter().all(|(key, a)| b.get(key).is_some_and(|b| equals(a, b))),
+        _ => false,
+    }
+}
+
+/// Orders two values of compatible types, or returns `None` if they can't be ordered.
+pub fn compare(lhs: &Value, rhs: &Value) -> Option<Ordering> {
+    match (lhs, rhs) {
+        (Value::Integer(a), Value::Integer(b)) => Some(a.cmp(b)),
+        (Value::Integer(a), Value::Float(b)) => (*a as f64).partial_cmp(b),
+        (Value::Float(a), Value::Integer(b)) => a.partial_cmp(&(*b as f64)),
+        (Value::Float(a), Value::Float(b)) => a.partial_cmp(b),
+        (Value::Boolean(a), Value::Boolean(b)) => Some(a.cmp(b)),
+        (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+        (Value::Location(a), Value::Location(b)) => Some(a.cmp(b)),
+        (Value::Bytes(a), Value::Bytes(b)) => Some(a.cmp(b)),
+        (Value::Date(a), Value::Date(b)) => Some(a.cmp(b)),
+        (Value::List(a), Value::List(b)) => {
+            for (a, b) in a.iter().zip(b) {
+                match compare(a, b)? {
+                    Ordering::Equal => continue,
+                    ordering => return Some(ordering),
+                }
+            }
+
+            Some(a.len().cmp(&b.len()))
+        }
+        _ => None,
+    }
+}
+
+fn invalid(op: OperatorType, lhs: &Value, rhs: &Value) -> RuntimeError {
+    RuntimeError::InvalidOperation(format!("cannot apply '{}' to {} and {}", op.symbol(), lhs.type_name(), rhs.type_name()))
+}
+
+fn overflow(op: OperatorType) -> RuntimeError {
+    RuntimeError::InvalidOperation(format!("integer overflow in '{}'", op.symbol()))
+}
+
+/// Applies a binary operator to two evaluated operands. Integers are promoted to floats when mixed with them. The logical
+/// operators are handled here too, although the evaluator short-circuits them before getting this far.
+pub fn binary(op: OperatorType, lhs: Value, rhs: Value) -> Result<Value, RuntimeError> {
+    match op {
+        OperatorType::Add => add(lhs, rhs),
+        OperatorType::Subtract => subtract(lhs, rhs),
+        OperatorType::Multiply => multiply(lhs, rhs),
+        OperatorType::Divide => divide(lhs, rhs),
+        OperatorType::Modulo => modulo(lhs, rhs),
+        OperatorType::Exponent => exponent(lhs, rhs),
+        OperatorType::Equal => Ok(Value::Boolean(equals(&lhs, &rhs))),
+        OperatorType::NotEqual => Ok(Value::Boolean(!equals(&lhs, &rhs))),
+        OperatorType::GreaterThan | OperatorType::LessThan | OperatorType::GreaterThanOrEqual | OperatorType::LessThanOrEqual => {
+            let ordering = compare(&lhs, &rhs).ok_or_else(|| invalid(op, &lhs, &rhs))?;
+
+            Ok(Value::Boolean(match op {
+                OperatorType::GreaterThan => ordering.is_gt(),
+                OperatorType::LessThan => ordering.is_lt(),
+                OperatorType::GreaterThanOrEqual => ordering.is_ge(),
+                _ => ordering.is_le(),
+            }))
+        }
+        OperatorType::And => Ok(Value::Boolean(lhs.truthy() && rhs.truthy())),
+        OperatorType::Or => Ok(Value::Boolean(lhs.truthy() || rhs.truthy())),
+        _ => Err(invalid(op, &lhs, &rhs)),
+    }
+}
+
+/// Applies a prefix operator.
+pub fn unary(op: OperatorType, value: Value) -> Result<Value, RuntimeError> {
+    match (op, value) {
+        (OperatorType::Not, value) => Ok(Value::Boolean(!value.truthy())),
+        (OperatorType::Subtract, Value::Integer(int)) => int.checked_neg().map(Value::Integer).ok_or_else(|| overflow(op)),
+        (OperatorType::Subtract, Value::Float(float)) => Ok(Value::Float(-float)),
+        (op, value) => Err(RuntimeError::InvalidOperation(format!("cannot apply '{}' to {}", op.symbol(), value.type_name()))),
+    }
+}
+
+fn add(lhs: Value, rhs: Value) -> Result<Value, RuntimeError> {
+    match (lhs, rhs) {
+        (Value::Integer(a), Value::Integer(b)) => a.checked_add(b).map(Value::Integer).ok_or_else(|| overflow(OperatorType::Add)),
+        (Value::Integer(a), Value::Float(b)) | (Value::Float(b), Value::Integer(a)) => Ok(Value::Float(a as f64 + b)),
+        (Value::Float(a), Value::Float(b)) => Ok(Value::Float(a + b)),
+        (Value::String(a), Value::String(b)) => Ok(Value::String(a + &b)),
+        (Value::Bytes(mut a), Value::Bytes(b)) => {
+            a.extend(b);
+            Ok(Value::Bytes(a))
+        }
+        (Value::List(mut a), Value::List(b)) => {
+            a.extend(b);
+            Ok(Value::List(a))
+        }
+        (Value::Dict(mut a), Value::Dict(b)) => {
+            merge(&mut a, b);
+            Ok(Value::Dict(a))
+        }
+        (lhs, rhs) => Err(invalid(OperatorType::Add, &lhs, &rhs)),
+    }
+}
+
+/// Merges `from` into `into`, replacing existing keys with the values from `from`. Where both sides hold a dict under
+/// the same key, the two are merged recursively instead.
+pub fn merge(into: &mut Dict, from: Dict) {
+    for (key, value) in from {
+        match (into.get_mut(&key), value) {
+            (Some(Value::Dict(existing)), Value::Dict(value)) => merge(existing, value),
+            (_, value) => {
+                into.insert(key, value);
+            }
+        }
+    }
+}
+
+fn subtract(lhs: Value, rhs: Value) -> Result<Value, RuntimeError> {
+    match (lhs, rhs) {
+        (Value::Integer(a), Value::Integer(b)) => a.checked_sub(b).map(Value::Integer).ok_or_else(|| overflow(OperatorType::Subtract)),
+        (Value::Integer(a), Value::Float(b)) => Ok(Value::Float(a as f64 - b)),
+        (Value::Float(a), Value::Integer(b)) => Ok(Value::Float(a - b as f64)),
+        (Value::Float(a), Value::Float(b)) => Ok(Value::Float(a - b)),
+        (Value::Date(a), Value::Date(b)) => Ok(Value::Float((a - b).num_milliseconds() as f64 / 1000.0)),
+        // Removes each key of the right-hand dict whose value matches the left-hand side's
+        (Value::Dict(mut a), Value::Dict(b)) => {
+            a.retain(|key, value| !b.get(key).is_some_and(|pattern| matches_pattern(value, pattern)));
+            Ok(Value::Dict(a))
+        }
+        (Value::Dict(mut a), Value::String(key)) => {
+            a.shift_remove(&key);
+            Ok(Value::Dict(a))
+        }
+        (Value::Dict(mut a), Value::List(keys)) => {
+            a.retain(|key, _| !keys.iter().any(|i| matches!(i, Value::String(i) if i == key)));
+            Ok(Value::Dict(a))
+        }
+        (Value::List(mut a), Value::List(b)) => {
+            a.retain(|i| !b.iter().any(|j| equals(i, j)));
+            Ok(Value::List(a))
+        }
+        (lhs, rhs) => Err(invalid(OperatorType::Subtract, &lhs, &rhs)),
+    }
+}
+
+/// Whether `value` is matched by the value given for its key on the right-hand side of a dict subtraction. A regex
+/// matches text, numbers and booleans it's found in, as in `- { id: r'.*' }`, and anything else must be equal.
+fn matches_pattern(value: &Value, pattern: &Value) -> bool {
+    match (value, pattern) {
+        (Value::String(str), Value::Regex(regex)) => regex.is_match(str),
+        (value @ (Value::Integer(_) | Value::Float(_) | Value::Boolean(_)), Value::Regex(regex)) => regex.is_match(&value.to_string()),
+        (value, pattern) => equals(value, pattern),
+    }
+}
+
+fn repeat<T: Clone>(items: &[T], times: i64) -> Result<Vec<T>, RuntimeError> {
+    usize::try_from(times)
+        .map(|times| (0..times).flat_map(|_| items.iter().cloned()).collect())
+        .map_err(|_| RuntimeError::InvalidOperation("cannot repeat a negative number of times".to_owned()))
+}
+
+fn multiply(lhs: Value, rhs: Value) -> Result<Value, RuntimeError> {
+    match (lhs, rhs) {
+        (Value::Integer(a), Value::Integer(b)) => a.checked_mul(b).map(Value::Integer).ok_or_else(|| overflow(OperatorType::Multiply)),
+        (Value::Integer(a), Value::Float(b)) | (Value::Float(b), Value::Integer(a)) => Ok(Value::Float(a as f64 * b)),
+        (Value::Float(a), Value::Float(b)) => Ok(Value::Float(a * b)),
+        (Value::String(a), Value::Integer(b)) => Ok(Value::String(repeat(&[a], b)?.concat())),
+        (Value::List(a), Value::Integer(b)) => Ok(Value::List(repeat(&a, b)?)),
+        (lhs, rhs) => Err(invalid(OperatorType::Multiply, &lhs, &rhs)),
+    }
+}
+
+fn as_float(value: &Value) -> Option<f64> {
+    match value {
+        Value::Integer(int) => Some(*int as f64),
+        Value::Float(float) => Some(*float),
+        _ => None,
+    }
+}
+
+/// Integer division stays an integer when it divides exactly, and produces a float otherwise.
+fn divide(lhs: Value, rhs: Value) -> Result<Value, RuntimeError> {
+    match (&lhs, &rhs) {
+        (Value::Integer(_), Value::Integer(0)) => Err(RuntimeError::InvalidOperation("division by zero".to_owned())),
+        (Value::Integer(a), Value::Integer(b)) if a.checked_rem(*b) == Some(0) => Ok(Value::Integer(a / b)),
+        _ => match (as_float(&lhs), as_float(&rhs)) {
+            (Some(a), Some(b)) => Ok(Value::Float(a / b)),
+            _ => Err(invalid(OperatorType::Divide, &lhs, &rhs)),
+        }
+    }
+}
+
+fn modulo(lhs: Value, rhs: Value) -> Result<Value, RuntimeError> {
+    match (&lhs, &rhs) {
+        (Value::Integer(_), Value::Integer(0)) => Err(RuntimeError::InvalidOperation("division by zero".to_owned())),
+        (Value::Integer(a), Value::Integer(b)) => a.checked_rem(*b).map(Value::Integer).ok_or_else(|| overflow(OperatorType::Modulo)),
+        _ => match (as_float(&lhs), as_float(&rhs)) {
+            (Some(a), Some(b)) => Ok(Value::Float(a % b)),
+            _ => Err(invalid(OperatorType::Modulo, &lhs, &rhs)),
+        }
+    }
+}
+
+fn exponent(lhs: Value, rhs: Value) -> Result<Value, RuntimeError> {
+    match (&lhs, &rhs) {
+        (Value::Integer(a), Value::Integer(b)) if *b >= 0 => u32::try_from(*b).ok()
+            .and_then(|b| a.checked_pow(b))
+            .map(Value::Integer)
+            .ok_or_else(|| overflow(OperatorType::Exponent)),
+        _ => match (as_float(&lhs), as_float(&rhs)) {
+            (Some(a), Some(b)) => Ok(Value::Float(a.powf(b))),
+            _ => Err(invalid(OperatorType::Exponent, &lhs, &rhs)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::command::value::Dict;
+
+    #[test]
+    pub fn test_numeric_promotion() -> Result<(), RuntimeError> {
+        assert!(matches!(binary(OperatorType::Add, Value::Integer(1), Value::Integer(2))?, Value::Integer(3)));
+        assert!(matches!(binary(OperatorType::Add, Value::Integer(1), Value::Float(0.5))?, Value::Float(f) if f == 1.5));
+        assert!(matches!(binary(OperatorType::Divide, Value::Integer(6), Value::Integer(3))?, Value::Integer(2)));
+        assert!(matches!(binary(OperatorType::Divide, Value::Integer(7), Value::Integer(2))?, Value::Float(f) if f == 3.5));
+        assert!(matches!(binary(OperatorType::Exponent, Value::Integer(2), Value::Integer(-1))?, Value::Float(f) if f == 0.5));
+        assert!(binary(OperatorType::Multiply, Value::Integer(i64::MAX), Value::Integer(2)).is_err());
+        assert!(binary(OperatorType::Modulo, Value::Integer(1), Value::Integer(0)).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_dict_merge_and_removal() -> Result<(), RuntimeError> {
+        let mut user = Dict::new();
+        user.insert("name".to_owned(), Value::String("John Doe".to_owned()));
+        user.insert("id".to_owned(), Value::Integer(0));
+
+        let mut age = Dict::new();
+        age.insert("age".to_owned(), Value::Integer(25));
+
+        let Value::Dict(merged) = binary(OperatorType::Add, Value::Dict(user), Value::Dict(age))? else { panic!() };
+        assert_eq!(merged.keys().collect::<Vec<_>>(), ["name", "id", "age"]);
+
+        let mut id = Dict::new();
+        id.insert("id".to_owned(), Value::Integer(0));
+
+        let Value::Dict(removed) = binary(OperatorType::Subtract, Value::Dict(merged), Value::Dict(id))? else { panic!() };
+        assert_eq!(removed.keys().collect::<Vec<_>>(), ["name", "age"]);
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_comparison() -> Result<(), RuntimeError> {
+        assert!(matches!(binary(OperatorType::LessThan, Value::Integer(1), Value::Float(1.5))?, Value::Boolean(true)));
+        assert!(matches!(binary(OperatorType::Equal, Value::Integer(1), Value::Float(1.0))?, Value::Boolean(true)));
+        assert!(matches!(binary(OperatorType::GreaterThanOrEqual, Value::String("b".to_owned()), Value::String("a".to_owned()))?, Value::Boolean(true)));
+        assert!(binary(OperatorType::LessThan, Value::Integer(1), Value::String("a".to_owned())).is_err());
+
+        Ok(())
+    }
+}
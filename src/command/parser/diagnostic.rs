@@ -0,0 +1,104 @@
+use std::fmt::Display;
+
+use crate::command::parser::{Span, SyntaxError};
+
+/// An error which can point to the part of the source it's about
+pub trait Diagnostic: Display {
+    /// Where in `source` the error lies, if that's known
+    fn span_in(&self, source: &str) -> Option<Span>;
+
+    /// A suggestion on how to fix the error, shown beneath the diagnostic
+    fn hint(&self) -> Option<&'static str>;
+
+    /// The source the error lies in and its name, when that isn't the source being run, as for an error in a module
+    fn origin(&self) -> Option<(&str, &str)> {
+        None
+    }
+}
+
+impl Diagnostic for SyntaxError {
+    fn span_in(&self, source: &str) -> Option<Span> {
+        match self {
+            // Point just past the last thing written, as that's where something was missing
+            SyntaxError::UnexpectedEOF() => Some(Span::at(source, source.trim_end().len(), 1)),
+            err => err.span(),
+        }
+    }
+
+    fn hint(&self) -> Option<&'static str> {
+        SyntaxError::hint(self)
+    }
+}
+
+/// Formats an error for the user. Errors which point into the source show the offending line with the span underlined,
+/// in the form
+///
+/// ```text
+/// SyntaxError: Unexpected Token '?' at 1:6
+///  --> <prompt>:1:6
+///   |
+/// 1 | echo ?x
+///   |      ^
+///   = hint: text which isn't a name, number or operator must be quoted
+/// ```
+pub fn render(err: &impl Diagnostic, source: &str, name: &str) -> String {
+    let (source, name) = err.origin().unwrap_or((source, name));
+    let mut out = err.to_string();
+    let span = err.span_in(source);
+
+    if let Some(span) = span.filter(|span| span.start <= source.len()) {
+        let line_start = source[..span.start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = source[span.start..].find('\n').map_or(source.len(), |i| span.start + i);
+        let line = &source[line_start..line_end];
+
+        // Spans running onto later lines are underlined up to the end of the first
+        let width = source[span.start..span.end.clamp(span.start, line_end)].chars().count().max(1);
+        let gutter = " ".repeat(span.line.to_string().len());
+
+        out.push_str(&format!("\n{}--> {}:{}:{}", gutter, name, span.line, span.column));
+        out.push_str(&format!("\n{} |", gutter));
+        out.push_str(&format!("\n{} | {}", span.line, line));
+        out.push_str(&format!("\n{} | {}{}", gutter, " ".repeat(span.column as usize - 1), "^".repeat(width)));
+    }
+
+    if let Some(hint) = err.hint() {
+        out.push_str(&format!("\n{} = hint: {}", " ".repeat(span.map_or(0, |span| span.line.to_string().len())), hint));
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::command::parser::{parse_statements, tokenise};
+
+    fn diagnose(source: &str) -> String {
+        let err = tokenise(source).and_then(|tokens| parse_statements(&tokens)).unwrap_err();
+        render(&err, source, "test.esh")
+    }
+
+    #[test]
+    pub fn test_render() {
+        assert_eq!(diagnose("x = 1\necho ?x"), [
+            "SyntaxError: Unexpected Token '?' at 2:6",
+            " --> test.esh:2:6",
+            "  |",
+            "2 | echo ?x",
+            "  |      ^",
+            "  = hint: text which isn't a name, number or operator must be quoted",
+        ].join("\n"));
+
+        assert!(diagnose("x = [1, 2)").contains("1 | x = [1, 2)\n  |          ^\n"));
+        assert!(diagnose("ls(1, 2").contains("at 1:3"));
+        assert!(diagnose("1 +\n").contains("1 | 1 +\n  |    ^"));
+    }
+
+    #[test]
+    pub fn test_unexpected_token_position() {
+        let Err(SyntaxError::UnexpectedToken(lexeme, span)) = tokenise("1 +\n  2 + €") else { panic!() };
+
+        assert_eq!(lexeme, "€");
+        assert_eq!(span, Span { start: 10, end: 13, line: 2, column: 7 });
+    }
+}
@@ -1,12 +1,15 @@
+#[allow(clippy::module_inception)]
 mod parser;
 mod tokeniser;
-mod matchers;
+mod string;
 mod syntax_err;
+mod diagnostic;
 
 pub use parser::*;
 pub use tokeniser::*;
-pub use matchers::*;
+pub use string::*;
 pub use syntax_err::*;
+pub use diagnostic::*;
 
 #[cfg(test)]
 mod test {
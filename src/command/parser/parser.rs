@@ -1,14 +1,10 @@
+use regex::Regex;
+
+use crate::command::parser::string::{StringPart, StringToken};
 use crate::command::parser::syntax_err::SyntaxError;
-use crate::command::parser::tokeniser::{BracketType, OperatorType, Token, TokenType};
+use crate::command::parser::tokeniser::{tokenise_recovering, BracketType, KeywordType, OperatorType, Span, Token, TokenType};
 pub use crate::command::parser::tokeniser::tokenise;
 
-#[derive(Debug, Clone)]
-pub enum OpOrExpr {
-    Operator(OperatorType),
-    Expr(Box<ASTNode>),
-    Literal(LiteralToken),
-}
-
 #[derive(Debug, Clone)]
 pub enum KeyOrNoKey {
     Key(String, Box<ASTNode>),
@@ -19,350 +15,811 @@ pub enum KeyOrNoKey {
 pub enum LiteralToken {
     Symbol(String),
     String(String),
+    Integer(i64),
     Number(f64),
     Boolean(bool),
+    Regex(Regex),
+    Bytes(Vec<u8>),
+    /// An f-string, whose expressions are evaluated and joined with the text between them
+    Format(Vec<FormatPart>),
+}
+
+#[derive(Debug, Clone)]
+pub enum FormatPart {
+    Text(String),
+    Expression(Box<ASTNode>),
 }
 
 #[derive(Debug, Clone)]
 pub enum DictKey {
     Key(Box<ASTNode>, Box<ASTNode>),
     NoKey(Box<ASTNode>),
+    /// `...expr` splices the entries of another dict or list into the literal
+    Spread(Box<ASTNode>),
 }
 
 #[derive(Debug, Clone)]
 pub enum ASTNode {
-    Call(Box<ASTNode>, Vec<KeyOrNoKey>), //
+    /// A function called with its arguments, along with where the call is written, from the function to the closing
+    /// parenthesis. Errors raised by the call point there.
+    Call(Box<ASTNode>, Vec<KeyOrNoKey>, Span),
     Lambda(Vec<String>, Box<ASTNode>), //
-    Expression(Vec<OpOrExpr>), //
+    /// A value written out in the source, and where it's written
+    Literal(LiteralToken, Span),
+    /// A prefix operator applied to its operand, either `!` or `-`
+    Unary(OperatorType, Box<ASTNode>),
+    /// A binary operator applied to its left and right-hand sides, as arranged by precedence and associativity
+    Binary(OperatorType, Box<ASTNode>, Box<ASTNode>),
     Dict(Vec<DictKey>), //
     Index(Vec<Box<ASTNode>>), //
     Import(Vec<String>, Box<ASTNode>), //
-    // TODO: Define control-flow
+    Block(Vec<Box<ASTNode>>), //
+    If(Box<ASTNode>, Box<ASTNode>, Option<Box<ASTNode>>), //
+    For(String, Box<ASTNode>, Box<ASTNode>), //
+    Function(String, Vec<String>, Box<ASTNode>), //
+    Return(Option<Box<ASTNode>>), //
+    Try(Box<ASTNode>, Option<String>, Box<ASTNode>), //
+    /// A statement which couldn't be parsed, left in place of it by `parse_program`
+    Invalid(Span),
     Nothing,
 }
 
-pub fn get_enclosed_tokens(tokens: &[Token]) -> Result<&[Token], SyntaxError> {
-    let mut bracket_count: (isize, isize, isize, isize) = (0, 0, 0, 0);
-
-    for (a, i) in tokens.iter().enumerate() {
-        match i.token_type {
-            TokenType::OpenBracket(bracket) => match bracket {
-                BracketType::Parenthesis => bracket_count.0 += 1,
-                BracketType::Brace => bracket_count.1 += 1,
-                BracketType::Bracket => bracket_count.2 += 1,
-                BracketType::Angle => bracket_count.3 += 1,
-            },
-            TokenType::CloseBracket(bracket) => match bracket {
-                BracketType::Parenthesis => bracket_count.0 -= 1,
-                BracketType::Brace => bracket_count.1 -= 1,
-                BracketType::Bracket => bracket_count.2 -= 1,
-                BracketType::Angle => bracket_count.3 -= 1,
+/// Pairs up the brackets in a single pass, giving for each bracket the index of the one it's closed or opened by. A
+/// closing bracket which doesn't close the innermost open one is reported, as is the first bracket left open at the end.
+/// Brackets without a partner are left as `None`.
+pub fn match_brackets(tokens: &[Token]) -> (Vec<Option<usize>>, Vec<SyntaxError>) {
+    let mut partners = vec![None; tokens.len()];
+    let mut errors = Vec::new();
+    let mut open: Vec<usize> = Vec::new();
+
+    for (a, token) in tokens.iter().enumerate() {
+        match token.token_type {
+            TokenType::OpenBracket(_) => open.push(a),
+            // A bracket closing one further out closes everything opened since, as in `(a, [b)`
+            TokenType::CloseBracket(close) => match open.iter().rposition(|i| matches!(tokens[*i].token_type, TokenType::OpenBracket(bracket) if bracket == close)) {
+                Some(depth) => {
+                    if depth + 1 != open.len() {
+                        errors.push(SyntaxError::BracketMismatch(token.span()));
+                    }
+
+                    partners[open[depth]] = Some(a);
+                    partners[a] = Some(open[depth]);
+                    open.truncate(depth);
+                }
+                None => errors.push(SyntaxError::BracketMismatch(token.span())),
             },
-            _ => continue
+            _ => {}
         }
+    }
 
-        if bracket_count.0 < 0 || bracket_count.1 < 0 || bracket_count.2 < 0 || bracket_count.3 < 0 {
-            return Err(SyntaxError::BracketMismatch(tokens[0].line, tokens[0].column));
-        }
+    if let Some(bracket) = open.first() {
+        errors.push(SyntaxError::BracketMismatch(tokens[*bracket].span()));
+    }
 
-        if bracket_count.0 + bracket_count.1 + bracket_count.2 + bracket_count.3 == 0 {
-            return Ok(&tokens[1..a]);
-        }
+    (partners, errors)
+}
+
+/// Finds, for each token, whether the tokens from it onwards are the remaining parameters of a lambda, as in `b -> ...`
+/// or `b; c -> ...`. Working backwards means each chain of `a; b; ...` is only looked at once, however long it is.
+fn lambda_params(tokens: &[Token]) -> Vec<bool> {
+    let mut params = vec![false; tokens.len()];
+
+    for a in (0..tokens.len()).rev() {
+        params[a] = matches!(tokens[a].token_type, TokenType::Symbol(_)) && match tokens.get(a + 1).map(|token| &token.token_type) {
+            Some(TokenType::Lambda) => true,
+            Some(TokenType::Semicolon) => params.get(a + 2).copied().unwrap_or(false),
+            _ => false
+        };
     }
 
-    Err(SyntaxError::BracketMismatch(tokens[0].line, tokens[0].column))
+    params
+}
+
+/// A recursive-descent parser over a list of tokens, which reads expressions by precedence climbing (Pratt parsing) so
+/// that every token is looked at a constant number of times.
+struct Parser<'a> {
+    tokens: &'a [Token],
+    /// The partner of each bracket, as found by `match_brackets`
+    partners: Vec<Option<usize>>,
+    /// Which tokens start the parameters of a lambda, as found by `lambda_params`
+    lambdas: Vec<bool>,
+    position: usize,
+    /// Where the tokens being parsed end, which is the closing bracket around them or the end of the input
+    end: usize,
+    /// Whether a line break ends a statement, which it doesn't inside parentheses, lists or dicts
+    lines: bool,
+    /// Whether statements which can't be parsed are recorded in `errors` and skipped, rather than stopping the parser
+    recovering: bool,
+    errors: Vec<SyntaxError>,
 }
 
-pub fn top_level_split<F>(tokens: &[Token], predicate: F, keep_delimiter: bool) -> Result<Vec<&[Token]>, SyntaxError> where F: Fn(&Token) -> bool {
-    let mut sections: Vec<&[Token]> = Vec::new();
+impl<'a> Parser<'a> {
+    fn new(tokens: &'a [Token], partners: Vec<Option<usize>>, recovering: bool) -> Self {
+        Self { tokens, partners, lambdas: lambda_params(tokens), position: 0, end: tokens.len(), lines: true, recovering, errors: Vec::new() }
+    }
 
-    let mut start = 0;
-    let mut bracket_count: (isize, isize, isize, isize) = (0, 0, 0, 0);
+    /// Whether the parameters of a lambda start at the given token. They can't run past `end`, which is a bracket.
+    fn is_lambda(&self, position: usize) -> bool {
+        position < self.end && self.lambdas[position]
+    }
 
-    for (a, i) in tokens.iter().enumerate() {
-        match i.token_type {
-            TokenType::OpenBracket(bracket) => match bracket {
-                BracketType::Parenthesis => bracket_count.0 += 1,
-                BracketType::Brace => bracket_count.1 += 1,
-                BracketType::Bracket => bracket_count.2 += 1,
-                BracketType::Angle => bracket_count.3 += 1,
-            },
-            TokenType::CloseBracket(bracket) => match bracket {
-                BracketType::Parenthesis => bracket_count.0 -= 1,
-                BracketType::Brace => bracket_count.1 -= 1,
-                BracketType::Bracket => bracket_count.2 -= 1,
-                BracketType::Angle => bracket_count.3 -= 1,
-            },
-            _ => {}
-        }
+    fn peek(&self) -> Option<&'a Token> {
+        self.tokens[..self.end].get(self.position)
+    }
 
-        if bracket_count.0 < 0 || bracket_count.1 < 0 || bracket_count.2 < 0 || bracket_count.3 < 0 {
-            return Err(SyntaxError::BracketMismatch(tokens[0].line, tokens[0].column));
+    fn peek_type(&self) -> Option<&'a TokenType> {
+        self.peek().map(|token| &token.token_type)
+    }
+
+    /// Moves past the current token if it's what's expected, failing otherwise
+    fn expect(&mut self, expected: impl Fn(&TokenType) -> bool) -> Result<&'a Token, SyntaxError> {
+        match self.peek() {
+            Some(token) if expected(&token.token_type) => {
+                self.position += 1;
+                Ok(token)
+            }
+            _ => Err(self.unexpected())
         }
+    }
 
-        if bracket_count.0 + bracket_count.1 + bracket_count.2 + bracket_count.3 == 0 && predicate(i) {
-            let s = start;
-            start = a + 1;
+    /// The error for the current token being out of place, or for there being nothing left
+    fn unexpected(&self) -> SyntaxError {
+        match self.peek() {
+            // Brackets without a partner have been reported by `match_brackets` already
+            Some(token) if matches!(token.token_type, TokenType::OpenBracket(_) | TokenType::CloseBracket(_)) && self.partners[self.position].is_none() => SyntaxError::BracketMismatch(token.span()),
+            Some(token) => SyntaxError::InvalidSyntax(token.span()),
+            None => match self.tokens.get(self.end) {
+                Some(close) => SyntaxError::InvalidSyntax(close.span()),
+                None => SyntaxError::UnexpectedEOF(),
+            }
+        }
+    }
 
-            sections.push(if keep_delimiter {
-                &tokens[s..start]
-            } else {
-                &tokens[s..a]
-            });
+    /// Whether the current token starts a later line than the one before it ends on, in a place where that matters
+    fn line_break(&self) -> bool {
+        match (self.lines, self.position.checked_sub(1).map(|a| &self.tokens[a]), self.peek()) {
+            (true, Some(previous), Some(next)) => next.line > previous.line + previous.lexeme.matches('\n').count() as i64,
+            _ => false
         }
     }
 
-    if start < tokens.len() {
-        sections.push(&tokens[start..]);
+    /// Whether the current token is a closing bracket which doesn't close anything
+    fn stray_bracket(&self) -> bool {
+        matches!(self.peek_type(), Some(TokenType::CloseBracket(_))) && self.partners[self.position].is_none()
     }
 
-    Ok(sections)
-}
+    /// Parses what lies between the bracket at the current position and the one which closes it, all of which `inner`
+    /// must take up. Line breaks separate statements inside the brackets if they enclose a block.
+    fn enclosed<T>(&mut self, block: bool, inner: impl FnOnce(&mut Self) -> Result<T, SyntaxError>) -> Result<T, SyntaxError> {
+        let Some(close) = self.partners[self.position] else {
+            return Err(self.unexpected());
+        };
+
+        let (end, lines) = (self.end, self.lines);
+        (self.end, self.lines) = (close, block);
+        self.position += 1;
+
+        let result = inner(self).and_then(|value| match self.peek() {
+            Some(_) => Err(self.unexpected()),
+            None => Ok(value)
+        });
+
+        (self.end, self.lines) = (end, lines);
+        self.position = close + 1;
 
-fn parse_call(tokens: &[Token]) -> Result<ASTNode, SyntaxError> {
-    if tokens.len() < 3 || !matches!(tokens.last().unwrap().token_type, TokenType::CloseBracket(BracketType::Parenthesis)) {
-        return Err(SyntaxError::UnexpectedEOF());
-        // return Err(SyntaxError::InvalidSyntax(tokens[0].line, tokens[0].column));
+        result
     }
 
-    // find last top-level parenthesis
-    let mut bracket_count = 0isize;
-    let mut index = 0usize;
-    for (a, i) in tokens.iter().enumerate() {
-        if matches!(i.token_type, TokenType::OpenBracket(BracketType::Parenthesis)) {
-            if bracket_count == 0 {
-                index = a;
+    /// Parses statements up to the end of the block or input, which are separated by semicolons or line breaks. When
+    /// recovering, a statement which can't be parsed is left as `ASTNode::Invalid` and parsing resumes after it.
+    #[allow(clippy::vec_box)] // Matches the `ASTNode::Block` it's used to build
+    fn statements(&mut self) -> Result<Vec<Box<ASTNode>>, SyntaxError> {
+        let mut statements = Vec::new();
+
+        loop {
+            while matches!(self.peek_type(), Some(TokenType::Semicolon)) {
+                self.position += 1;
+            }
+
+            if self.peek().is_none() {
+                return Ok(statements);
+            }
+
+            let start = self.position;
+
+            match self.statement() {
+                Ok(statement) => statements.push(statement),
+                Err(err) if self.recovering => {
+                    if !matches!(err, SyntaxError::BracketMismatch(_)) {
+                        self.errors.push(err);
+                    }
+
+                    self.skip_statement(start);
+                    statements.push(Box::new(ASTNode::Invalid(span_of(&self.tokens[start..self.position.max(start + 1)]))));
+                }
+                Err(err) => return Err(err),
             }
-            bracket_count += 1;
-        } else if matches!(i.token_type, TokenType::CloseBracket(BracketType::Parenthesis)) {
-            bracket_count -= 1;
         }
     }
-    let (function, enclosed) = tokens.split_at(index);
 
-    if let Some(last) = function.last() {
-        if matches!(last.token_type, TokenType::Dot) {
-            return Err(SyntaxError::InvalidSyntax(last.line, last.column));
+    /// Parses an expression which must be followed by the end of the statement
+    fn statement(&mut self) -> Result<Box<ASTNode>, SyntaxError> {
+        let statement = self.expression(i8::MIN)?;
+
+        match self.peek_type() {
+            None => {}
+            Some(TokenType::Semicolon) => self.position += 1,
+            Some(_) if self.line_break() => {}
+            // A stray closing bracket ends the statement before it, as `match_brackets` reports it
+            Some(_) if self.stray_bracket() => self.position += 1,
+            Some(_) => return Err(self.unexpected())
         }
+
+        Ok(statement)
     }
 
-    let enclosed_tokens = top_level_split(get_enclosed_tokens(enclosed)?, |t| matches!(t.token_type, TokenType::Comma), false)?;
+    /// Moves past the statement starting at `start`, after an error in it. The statement ends at a semicolon or line
+    /// break outside of brackets, unless it's clearly unfinished, or at a stray closing bracket. A bracket which is never
+    /// closed swallows everything after it.
+    fn skip_statement(&mut self, start: usize) {
+        self.position = start;
+
+        while let Some(token) = self.peek() {
+            match (&token.token_type, self.partners[self.position]) {
+                (TokenType::OpenBracket(_), Some(close)) => self.position = close + 1,
+                (TokenType::OpenBracket(_), None) => self.position = self.end,
+                (TokenType::CloseBracket(_), None) => {
+                    self.position += 1;
+                    return;
+                }
+                // `a; b -> a + b` is a lambda, not two statements
+                (TokenType::Semicolon, _) if !self.is_lambda(self.position + 1) => {
+                    self.position += 1;
+                    return;
+                }
+                _ => self.position += 1,
+            }
+
+            let unfinished = matches!(self.tokens[self.position - 1].token_type, TokenType::Operator(_) | TokenType::Comma | TokenType::Colon | TokenType::Dot | TokenType::Spread | TokenType::Lambda)
+                || matches!(self.peek_type(), Some(TokenType::Operator(_) | TokenType::Dot | TokenType::Lambda | TokenType::Keyword(KeywordType::Else | KeywordType::Catch)));
 
-    let args: Vec<Result<KeyOrNoKey, SyntaxError>> = enclosed_tokens.iter()
-        .map(|i| if i.len() > 1 && matches!(i[1].token_type, TokenType::Colon) {
-            match parse(&i[2..]) {
-                Ok(node) => Ok(KeyOrNoKey::Key(i[0].to_string(), node)),
-                Err(e) => Err(e)
+            if self.line_break() && !unfinished {
+                return;
             }
-        } else {
-            match parse(i) {
-                Ok(node) => Ok(KeyOrNoKey::NoKey(node)),
-                Err(e) => Err(e)
+        }
+    }
+
+    /// Parses an expression whose binary operators bind at least as tightly as `min_precedence`
+    fn expression(&mut self, min_precedence: i8) -> Result<Box<ASTNode>, SyntaxError> {
+        let mut lhs = self.operand()?;
+
+        // `!` is only ever a prefix
+        while let Some(&TokenType::Operator(op)) = self.peek_type().filter(|token| !matches!(token, TokenType::Operator(OperatorType::Not))) {
+            if op.precedence() < min_precedence {
+                break;
             }
-        }).collect();
 
-    if let Some(Err(e)) = args.iter().find(|i| i.is_err()) {
-        return Err(e.clone());
+            self.position += 1;
+
+            let rhs = self.expression(if op.is_right_associative() { op.precedence() } else { op.precedence() + 1 })?;
+            lhs = Box::new(ASTNode::Binary(op, lhs, rhs));
+        }
+
+        Ok(lhs)
     }
 
-    let args: Vec<KeyOrNoKey> = args.into_iter().map(|i| i.unwrap()).collect();
+    /// Parses a single operand, along with any prefix operators before it and the calls and indices after it
+    fn operand(&mut self) -> Result<Box<ASTNode>, SyntaxError> {
+        let start = self.position;
 
-    Ok(ASTNode::Call(parse(function)?, args))
-}
+        let Some(token) = self.peek() else {
+            return Err(self.unexpected());
+        };
+
+        let literal = |literal| Box::new(ASTNode::Literal(literal, token.span()));
+
+        let operand = match &token.token_type {
+            // `!` binds tighter than any binary operator, while `-` binds looser than `^` so that `-2 ^ 2` is `-(2 ^ 2)`
+            TokenType::Operator(op @ (OperatorType::Not | OperatorType::Subtract)) => {
+                self.position += 1;
+
+                let precedence = match op {
+                    OperatorType::Not => op.precedence(),
+                    _ => OperatorType::Exponent.precedence(),
+                };
 
-fn parse_expr(tokens: &[Token]) -> Result<ASTNode, SyntaxError> {
-    // If no operators, then it's a single value, so continue
-    if !tokens.iter().any(|i| matches!(i.token_type, TokenType::Operator(_))) {
-        if tokens.len() == 1 {
-            return match &tokens[0].token_type {
-                TokenType::Symbol(symbol) => Ok(ASTNode::Expression(vec![OpOrExpr::Literal(LiteralToken::Symbol(symbol.clone()))])),
-                TokenType::String(string) => Ok(ASTNode::Expression(vec![OpOrExpr::Literal(LiteralToken::String(string.clone()))])),
-                TokenType::Number(number) => Ok(ASTNode::Expression(vec![OpOrExpr::Literal(LiteralToken::Number(*number))])),
-                TokenType::Boolean(boolean) => Ok(ASTNode::Expression(vec![OpOrExpr::Literal(LiteralToken::Boolean(*boolean))])),
-                _ => Err(SyntaxError::InvalidSyntax(tokens[0].line, tokens[0].column))
+                return Ok(Box::new(ASTNode::Unary(*op, self.expression(precedence)?)));
             }
+            TokenType::Keyword(keyword) if !matches!(keyword, KeywordType::Else | KeywordType::Catch) => return self.keyword(*keyword),
+            TokenType::Lambda => return self.lambda(),
+            TokenType::Symbol(_) if self.is_lambda(self.position) => return self.lambda(),
+            TokenType::Symbol(symbol) => literal(LiteralToken::Symbol(symbol.clone())),
+            TokenType::String(string) => literal(string_literal(string, token.span())?),
+            TokenType::Integer(int) => literal(LiteralToken::Integer(*int)),
+            TokenType::Number(number) => literal(LiteralToken::Number(*number)),
+            TokenType::Boolean(boolean) => literal(LiteralToken::Boolean(*boolean)),
+            TokenType::OpenBracket(BracketType::Parenthesis) => self.enclosed(false, |parser| parser.expression(i8::MIN))?,
+            TokenType::OpenBracket(BracketType::Brace) => Box::new(self.dict()?),
+            // A leading dot indexes into the value piped in, as in `| .users`
+            TokenType::Dot => Box::new(ASTNode::Index(vec![Box::new(ASTNode::Nothing)])),
+            _ => return Err(self.unexpected())
+        };
+
+        // Brackets have been moved past already, while a leading dot is left for `postfix` to read the key after it
+        if !matches!(token.token_type, TokenType::OpenBracket(_) | TokenType::Dot) {
+            self.position += 1;
         }
-        return Err(SyntaxError::InvalidSyntax(tokens[0].line, tokens[0].column));
+
+        self.postfix(operand, start)
     }
 
-    let operands = top_level_split(tokens, |t| matches!(t.token_type, TokenType::Operator(_)), true)?;
+    /// Applies the calls and indices following an operand, which starts at the token `start`, to it, as in `f(x).y`
+    fn postfix(&mut self, mut operand: Box<ASTNode>, start: usize) -> Result<Box<ASTNode>, SyntaxError> {
+        loop {
+            match self.peek_type() {
+                Some(TokenType::Dot) => {
+                    self.position += 1;
 
-    let (last, rest) = operands.split_last().unwrap();
+                    let mut indices = match *operand {
+                        ASTNode::Index(indices) => indices,
+                        operand => vec![Box::new(operand)],
+                    };
 
-    Ok(ASTNode::Expression(rest.iter()
-        .map(|i| i.split_last().unwrap())
-        .map(|(op, i)| match parse(i) {
-            Ok(expr) => {
-                match op.token_type {
-                    TokenType::Operator(op) => Ok(vec![OpOrExpr::Expr(expr), OpOrExpr::Operator(op)]),
-                    _ => Err(SyntaxError::InvalidSyntax(tokens[0].line, tokens[0].column))
+                    indices.extend(self.index()?.into_iter().map(Box::new));
+                    operand = Box::new(ASTNode::Index(indices));
                 }
-            },
-            Err(err) => Err(err)
-        })
-        .chain(std::iter::once(match parse(last) {
-            Ok(expr) => Ok(vec![OpOrExpr::Expr(expr)]),
-            Err(err) => Err(err)
-        }))
-        .into_iter()
-        .flatten()
-        .flatten()
-        .collect::<Vec<OpOrExpr>>()))
-}
-
-fn parse_dict(tokens: &[Token]) -> Result<ASTNode, SyntaxError> {
-    // Dict: { expr: expr... }
-    if let Some(token) = tokens.get(0) {
-        if !matches!(token.token_type, TokenType::OpenBracket(BracketType::Brace)) {
-            return Err(SyntaxError::InvalidSyntax(token.line, token.column));
+                // A parenthesis on the next line starts a statement of its own
+                Some(TokenType::OpenBracket(BracketType::Parenthesis)) if !self.line_break() => {
+                    let args = self.enclosed(false, Self::arguments)?;
+                    operand = Box::new(ASTNode::Call(operand, args, span_of(&self.tokens[start..self.position])));
+                }
+                _ => return Ok(operand)
+            }
         }
     }
 
-    let enclosed_tokens = top_level_split(get_enclosed_tokens(tokens)?, |t| matches!(t.token_type, TokenType::Comma), false)?;
+    /// Parses the key following a dot, or keys in the case of `.0.1`
+    fn index(&mut self) -> Result<Vec<ASTNode>, SyntaxError> {
+        let Some(token) = self.peek() else {
+            return Err(self.unexpected());
+        };
+
+        let literal = |literal| ASTNode::Literal(literal, token.span());
+
+        let keys = match &token.token_type {
+            // Names are keys in their own right, whereas `.(name)` looks up the key held by a variable
+            TokenType::Symbol(name) => vec![literal(LiteralToken::String(name.clone()))],
+            // `.0.1` is read as a number by the lexer
+            TokenType::Number(_) => token.lexeme.split('.')
+                .map(|position| position.parse().map(|position| literal(LiteralToken::Integer(position))).map_err(|_| SyntaxError::InvalidSyntax(token.span())))
+                .collect::<Result<_, _>>()?,
+            TokenType::Integer(int) => vec![literal(LiteralToken::Integer(*int))],
+            TokenType::String(string) => vec![literal(string_literal(string, token.span())?)],
+            TokenType::OpenBracket(BracketType::Parenthesis) => return Ok(vec![*self.enclosed(false, |parser| parser.expression(i8::MIN))?]),
+            _ => return Err(self.unexpected())
+        };
+
+        self.position += 1;
+        Ok(keys)
+    }
 
-    let dict = enclosed_tokens.into_iter().map(|i| -> Result<DictKey, SyntaxError> {
-        if let Some(pos) = i.iter().position(|i| matches!(i.token_type, TokenType::Colon)) {
-            let (key, value) = i.split_at(pos);
+    /// Parses the arguments of a call, some of which may be named, as in `head(lines: 2)`
+    fn arguments(&mut self) -> Result<Vec<KeyOrNoKey>, SyntaxError> {
+        let mut args = Vec::new();
 
-            Ok(DictKey::Key(parse(key)?, parse(&value[1..])?))
-        } else {
-            Ok(DictKey::NoKey(parse(i)?))
+        while let Some(token) = self.peek() {
+            args.push(match (&token.token_type, self.peek_type_at(1)) {
+                (TokenType::Symbol(name), Some(TokenType::Colon)) => {
+                    self.position += 2;
+                    KeyOrNoKey::Key(name.clone(), self.expression(i8::MIN)?)
+                }
+                _ => KeyOrNoKey::NoKey(self.expression(i8::MIN)?)
+            });
+
+            self.separator()?;
         }
-    }).collect::<Vec<_>>();
 
-    if let Some(Err(e)) = dict.iter().find(|i| i.is_err()) {
-        return Err(e.clone());
+        Ok(args)
     }
 
-    Ok(ASTNode::Dict(dict.into_iter().map(|i| i.unwrap()).collect()))
-}
+    fn peek_type_at(&self, offset: usize) -> Option<&'a TokenType> {
+        self.tokens[..self.end].get(self.position + offset).map(|token| &token.token_type)
+    }
 
-fn parse_index(tokens: &[Token]) -> Result<ASTNode, SyntaxError> {
-    // Index: expr1.expr2.expr3
-    if tokens.len() <= 1 || !tokens.iter().any(|i| matches!(i.token_type, TokenType::Dot)) {
-        return Err(SyntaxError::InvalidSyntax(tokens[0].line, tokens[0].column));
+    /// Moves past the comma after an item in a list, which may be left out after the last one
+    fn separator(&mut self) -> Result<(), SyntaxError> {
+        match self.peek_type() {
+            None => Ok(()),
+            Some(TokenType::Comma) => {
+                self.position += 1;
+                Ok(())
+            }
+            Some(_) => Err(self.unexpected())
+        }
     }
 
-    let indices = top_level_split(tokens, |t| matches!(t.token_type, TokenType::Dot), false)?;
+    fn dict(&mut self) -> Result<ASTNode, SyntaxError> {
+        // Dict: { expr: expr... }
+        self.enclosed(false, |parser| {
+            // `{:}` is the empty dict, as it's printed
+            if let (Some(TokenType::Colon), None) = (parser.peek_type(), parser.peek_type_at(1)) {
+                parser.position += 1;
+                return Ok(ASTNode::Dict(vec![]));
+            }
 
-    let mut indices = indices.into_iter().map(parse);
+            let mut dict = Vec::new();
+
+            while parser.peek().is_some() {
+                dict.push(match parser.peek_type() {
+                    Some(TokenType::Spread) => {
+                        parser.position += 1;
+                        DictKey::Spread(parser.expression(i8::MIN)?)
+                    }
+                    _ => {
+                        let key = parser.expression(i8::MIN)?;
+
+                        match parser.peek_type() {
+                            Some(TokenType::Colon) => {
+                                parser.position += 1;
+                                DictKey::Key(key, parser.expression(i8::MIN)?)
+                            }
+                            _ => DictKey::NoKey(key)
+                        }
+                    }
+                });
+
+                parser.separator()?;
+            }
 
-    if indices.any(|i| i.is_err()) {
-        return Err(SyntaxError::InvalidSyntax(tokens[0].line, tokens[0].column));
+            Ok(ASTNode::Dict(dict))
+        })
     }
 
-    Ok(ASTNode::Index(indices.map(|i| i.unwrap()).collect()))
-}
-
-fn parse_lambda(tokens: &[Token]) -> Result<ASTNode, SyntaxError> {
-    // Lambdas: arg1; arg2; ...; argn -> body
+    fn lambda(&mut self) -> Result<Box<ASTNode>, SyntaxError> {
+        // Lambdas: arg1; arg2; ...; argn -> body
+        let mut args = Vec::new();
 
-    if let Some(lambda) = tokens.iter().position(|i| matches!(i.token_type, TokenType::Lambda)) {
-        let (args, body) = tokens.split_at(lambda);
+        while let Some(TokenType::Symbol(arg)) = self.peek_type() {
+            args.push(arg.clone());
+            self.position += 1;
 
-        let args = top_level_split(args, |t| matches!(t.token_type, TokenType::Semicolon), false)?;
-
-        if args.iter().any(|i| i.len() != 1 || !matches!(i[0].token_type, TokenType::Symbol(_))) {
-            return Err(SyntaxError::InvalidSyntax(tokens[0].line, tokens[0].column));
+            if matches!(self.peek_type(), Some(TokenType::Semicolon)) {
+                self.position += 1;
+            }
         }
 
-        let args: Vec<String> = args.iter().map(|i| i[0].lexeme.to_owned()).collect();
+        self.expect(|token| matches!(token, TokenType::Lambda))?;
 
-        let body = parse(&body[1..])?;
+        // The body extends to the end of the expression, so `f = x -> x + 1` assigns the whole lambda
+        Ok(Box::new(ASTNode::Lambda(args, self.expression(i8::MIN)?)))
+    }
 
-        Ok(ASTNode::Lambda(args, body))
-    } else {
-        Err(SyntaxError::InvalidSyntax(tokens[0].line, tokens[0].column))
+    /// Parses a `{ ... }` block of statements
+    fn block(&mut self) -> Result<Box<ASTNode>, SyntaxError> {
+        match self.peek_type() {
+            Some(TokenType::OpenBracket(BracketType::Brace)) => Ok(Box::new(ASTNode::Block(self.enclosed(true, Self::statements)?))),
+            _ => Err(self.unexpected())
+        }
     }
-}
 
-pub fn parse(tokens: &[Token]) -> Result<Box<ASTNode>, SyntaxError> {
-    if let Some(token) = tokens.get(0) {
-        if matches!(token.token_type, TokenType::OpenBracket(BracketType::Parenthesis)) {
-            if let Ok(node) = get_enclosed_tokens(tokens) {
-                return parse(node);
-            }
+    fn symbol(&mut self) -> Result<String, SyntaxError> {
+        match self.expect(|token| matches!(token, TokenType::Symbol(_)))?.token_type {
+            TokenType::Symbol(ref symbol) => Ok(symbol.clone()),
+            _ => unreachable!()
         }
     }
 
-    if let Ok(lambda) = parse_lambda(tokens) {
-        return Ok(Box::new(lambda));
+    /// Moves past a name which is only a keyword in one place, such as the `in` of a `for` loop
+    fn contextual_keyword(&mut self, keyword: &str) -> Result<(), SyntaxError> {
+        self.expect(|token| matches!(token, TokenType::Symbol(symbol) if symbol == keyword)).map(|_| ())
     }
 
-    if let Ok(dict) = parse_dict(tokens) {
-        return Ok(Box::new(dict));
+    /// Parses a list of names, as in `(a, b)` or `{ a, b }`
+    fn names(&mut self) -> Result<Vec<String>, SyntaxError> {
+        self.enclosed(false, |parser| {
+            let mut names = Vec::new();
+
+            while parser.peek().is_some() {
+                names.push(parser.symbol()?);
+                parser.separator()?;
+            }
+
+            Ok(names)
+        })
     }
 
-    if let Ok(index) = parse_index(tokens) {
-        return Ok(Box::new(index));
+    fn keyword(&mut self, keyword: KeywordType) -> Result<Box<ASTNode>, SyntaxError> {
+        self.position += 1;
+
+        Ok(Box::new(match keyword {
+            KeywordType::If => {
+                // If: if cond { ... } else if cond { ... } else { ... }
+                let condition = self.expression(i8::MIN)?;
+                let body = self.block()?;
+
+                let alternative = match self.peek_type() {
+                    Some(TokenType::Keyword(KeywordType::Else)) => {
+                        self.position += 1;
+
+                        match self.peek_type() {
+                            Some(TokenType::Keyword(KeywordType::If)) => Some(self.operand()?),
+                            _ => Some(self.block()?)
+                        }
+                    }
+                    _ => None
+                };
+
+                ASTNode::If(condition, body, alternative)
+            }
+            KeywordType::For => {
+                // For: for name in iterable { ... }
+                let name = self.symbol()?;
+                self.contextual_keyword("in")?;
+
+                ASTNode::For(name, self.expression(i8::MIN)?, self.block()?)
+            }
+            KeywordType::Function => {
+                // Function: function name(arg1, arg2) { ... }
+                let name = self.symbol()?;
+
+                match self.peek_type() {
+                    Some(TokenType::OpenBracket(BracketType::Parenthesis)) => ASTNode::Function(name, self.names()?, self.block()?),
+                    _ => return Err(self.unexpected())
+                }
+            }
+            KeywordType::Import => match self.peek_type() {
+                // Import: import { name1, name2 } from 'module.esh', or import 'module.esh' for everything it defines
+                Some(TokenType::OpenBracket(BracketType::Brace)) => {
+                    let names = self.names()?;
+                    self.contextual_keyword("from")?;
+
+                    ASTNode::Import(names, self.expression(i8::MIN)?)
+                }
+                _ => ASTNode::Import(vec![], self.expression(i8::MIN)?)
+            },
+            KeywordType::Try => {
+                // Try: try { ... } catch err { ... }, where the name of the error may be left out
+                let body = self.block()?;
+                self.expect(|token| matches!(token, TokenType::Keyword(KeywordType::Catch)))?;
+
+                let name = match self.peek_type() {
+                    Some(TokenType::Symbol(_)) => Some(self.symbol()?),
+                    _ => None
+                };
+
+                ASTNode::Try(body, name, self.block()?)
+            }
+            KeywordType::Return => match self.peek_type() {
+                None | Some(TokenType::Semicolon) => ASTNode::Return(None),
+                Some(_) if self.line_break() || self.stray_bracket() => ASTNode::Return(None),
+                Some(_) => ASTNode::Return(Some(self.expression(i8::MIN)?))
+            },
+            // Only ever read as part of an `if` or `try`
+            KeywordType::Else | KeywordType::Catch => unreachable!(),
+        }))
     }
+}
 
-    if let Ok(call) = parse_call(tokens) {
-        return Ok(Box::new(call));
+/// The literal a string token stands for. `i'...'` keys become regexes which match the whole of a key in any case.
+fn string_literal(string: &StringToken, span: Span) -> Result<LiteralToken, SyntaxError> {
+    let regex = |pattern: &str| Regex::new(pattern).map_err(|err| {
+        // The last line of the message says what's wrong, while the rest repeats the pattern
+        let reason = err.to_string().lines().last().unwrap_or_default().trim_start_matches("error: ").to_owned();
+        SyntaxError::InvalidString(format!("invalid regex: {}", reason), span)
+    });
+
+    Ok(match string {
+        StringToken::Text(text) => LiteralToken::String(text.clone()),
+        StringToken::Regex(pattern) => LiteralToken::Regex(regex(pattern)?),
+        StringToken::Insensitive(key) => LiteralToken::Regex(regex(&format!("(?i)^{}$", regex::escape(key)))?),
+        StringToken::Bytes(bytes) => LiteralToken::Bytes(bytes.clone()),
+        StringToken::Format(parts) => LiteralToken::Format(parts.iter()
+            .map(|part| match part {
+                StringPart::Text(text) => Ok(FormatPart::Text(text.clone())),
+                StringPart::Expression(source, outer) => interpolation(source, *outer).map(FormatPart::Expression),
+            })
+            .collect::<Result<_, _>>()?),
+    })
+}
+
+/// Parses an expression interpolated into an f-string, found at `outer` in the input
+fn interpolation(source: &str, outer: Span) -> Result<Box<ASTNode>, SyntaxError> {
+    let mut tokens = tokenise(source).map_err(|err| err.within(outer))?;
+
+    for token in &mut tokens {
+        let span = token.span().within(outer);
+        (token.index, token.line, token.column) = (span.start, span.line, span.column);
     }
 
-    if let Ok(expr) = parse_expr(tokens) {
-        return Ok(Box::new(expr));
+    parse(&tokens).map_err(|err| match err {
+        SyntaxError::UnexpectedEOF() => SyntaxError::InvalidString("the expression ends part way through".to_owned(), outer),
+        err => err,
+    })
+}
+
+/// The span from the start of the first token to the end of the last
+fn span_of(tokens: &[Token]) -> Span {
+    Span { end: tokens.last().map_or(0, |token| token.span().end), ..tokens[0].span() }
+}
+
+/// Parses a sequence of statements, such as the contents of a block or a whole module.
+pub fn parse_statements(tokens: &[Token]) -> Result<Vec<Box<ASTNode>>, SyntaxError> {
+    let (partners, errors) = match_brackets(tokens);
+
+    if let Some(err) = errors.into_iter().next() {
+        return Err(err);
     }
 
-    Err(SyntaxError::InvalidSyntax(tokens[0].line, tokens[0].column))
+    Parser::new(tokens, partners, false).statements()
+}
+
+/// A whole file parsed by `parse_program`, with as much of it understood as possible
+#[derive(Debug, Clone)]
+pub struct Program {
+    /// The file's statements, with those which couldn't be parsed left as `ASTNode::Invalid`
+    pub statements: Vec<Box<ASTNode>>,
+    /// Every error found, in the order they appear in the source
+    pub errors: Vec<SyntaxError>,
+}
+
+/// Parses a whole file without stopping at the first error. A statement which can't be parsed is recorded and skipped,
+/// resuming at the next semicolon, line break or closing bracket, so that every error can be reported at once.
+pub fn parse_program(source: &str) -> Program {
+    let (tokens, mut errors) = tokenise_recovering(source);
+    let (partners, bracket_errors) = match_brackets(&tokens);
+    errors.extend(bracket_errors);
+
+    let mut parser = Parser::new(&tokens, partners, true);
+    let statements = parser.statements().unwrap_or_else(|err| {
+        errors.push(err);
+        Vec::new()
+    });
+
+    errors.extend(parser.errors);
+    errors.sort_by_key(SyntaxError::position);
+
+    Program { statements, errors }
+}
+
+/// Parses a single statement
+pub fn parse(tokens: &[Token]) -> Result<Box<ASTNode>, SyntaxError> {
+    let (partners, errors) = match_brackets(tokens);
+
+    if let Some(err) = errors.into_iter().next() {
+        return Err(err);
+    }
+
+    let mut parser = Parser::new(tokens, partners, false);
+    let statement = parser.statement()?;
+
+    match parser.peek() {
+        Some(_) => Err(parser.unexpected()),
+        None => Ok(statement)
+    }
 }
 
 #[cfg(test)]
 mod test {
     #[test]
-    pub fn test_get_enclosed_content() {
+    pub fn test_match_brackets() {
         use super::*;
         use crate::command::parser::tokeniser::tokenise;
 
         let tokens = tokenise("(\"Hi\", \"World\"), \"hello\"").unwrap();
-        let enclosed = get_enclosed_tokens(&tokens[0..]).unwrap();
+        let (partners, errors) = match_brackets(&tokens);
 
-        dbg!(&enclosed);
+        assert!(errors.is_empty());
+        assert_eq!(partners[0], Some(4));
+        assert_eq!(partners[4], Some(0));
 
-        assert_eq!(enclosed.len(), 3);
+        let tokens = tokenise("f(a, [b) ]").unwrap();
+        let (partners, errors) = match_brackets(&tokens);
+
+        assert_eq!(partners[1], Some(6));
+        assert_eq!(partners[4], None);
+        assert!(matches!(errors.as_slice(), [SyntaxError::BracketMismatch(Span { column: 8, .. }), SyntaxError::BracketMismatch(Span { column: 10, .. })]));
     }
 
     #[test]
-    pub fn test_top_level_split() {
+    pub fn test_parse_statements() {
         use super::*;
         use crate::command::parser::tokeniser::tokenise;
 
-        let tokens = tokenise("1, (1 + 2, 3), 2, 3").unwrap();
-        let sections = top_level_split(&tokens, |t| matches!(t.token_type, TokenType::Comma), false)
-            .unwrap();
+        let tokens = tokenise("a = 1; f = b; c -> b + c\nls()\n  | keys\nif a {\n  print(a)\n}\nelse { { 1,\n 2 } }").unwrap();
+        let statements = parse_statements(&tokens).unwrap();
+
+        assert_eq!(statements.len(), 4);
+        assert!(matches!(*statements[1], ASTNode::Binary(OperatorType::Assign, _, ref lambda) if matches!(**lambda, ASTNode::Lambda(ref args, _) if args == &["b", "c"])));
 
-        dbg!(&sections);
+        // A whole chain of names separated by semicolons is a lambda's parameters if an arrow follows it
+        let statements = parse_statements(&tokenise("a; b; c; d -> c + d").unwrap()).unwrap();
+        assert!(matches!(statements.as_slice(), [lambda] if matches!(**lambda, ASTNode::Lambda(ref args, _) if args == &["a", "b", "c", "d"])));
+        assert_eq!(parse_statements(&tokenise(&"x; ".repeat(1000)).unwrap()).unwrap().len(), 1000);
 
-        assert_eq!(sections.len(), 4);
+        // A parenthesis on a line of its own isn't a call
+        assert_eq!(parse_statements(&tokenise("ls\n(1 + 2)").unwrap()).unwrap().len(), 2);
+        assert!(matches!(parse_statements(&tokenise("a = 1 2").unwrap()), Err(SyntaxError::InvalidSyntax(Span { column: 7, .. }))));
     }
 
     #[test]
-    pub fn test_top_level_split_keep_delimiter() {
+    pub fn test_parse_postfix() {
         use super::*;
         use crate::command::parser::tokeniser::tokenise;
 
-        let tokens = tokenise("1, (1 + 2, 3), 2, 3").unwrap();
-        let sections = top_level_split(&tokens, |t| matches!(t.token_type, TokenType::Comma), true)
-            .unwrap();
+        // Calls and indices bind tighter than any operator, so this is `1 + ((f(x)).y)`
+        let ast = parse(&tokenise("1 + f(x).y").unwrap()).unwrap();
 
-        dbg!(&sections);
+        let ASTNode::Binary(OperatorType::Add, _, index) = *ast else { panic!() };
+        let ASTNode::Index(indices) = *index else { panic!() };
 
-        assert_eq!(sections.len(), 4);
+        assert!(matches!(indices.as_slice(), [call, _] if matches!(**call, ASTNode::Call(_, _, Span { start: 4, end: 8, .. }))));
+
+        // A method-like call is a call of the index
+        assert!(matches!(*parse(&tokenise("a.b(c)").unwrap()).unwrap(), ASTNode::Call(ref function, ..) if matches!(**function, ASTNode::Index(_))));
     }
 
     #[test]
-    pub fn test_top_level_split_empty() {
+    pub fn test_parse_operators() {
         use super::*;
         use crate::command::parser::tokeniser::tokenise;
 
-        let tokens = tokenise("1").unwrap();
-        let sections = top_level_split(&tokens, |t| matches!(t.token_type, TokenType::Comma), false)
-            .unwrap();
-
-        dbg!(&sections);
+        // Writes out the tree with every operator application in parentheses
+        fn tree(node: &ASTNode) -> String {
+            match node {
+                ASTNode::Binary(op, lhs, rhs) => format!("({} {} {})", tree(lhs), op.symbol(), tree(rhs)),
+                ASTNode::Unary(op, operand) => format!("({}{})", op.symbol(), tree(operand)),
+                ASTNode::Literal(LiteralToken::Symbol(name), _) => name.clone(),
+                ASTNode::Literal(LiteralToken::Integer(int), _) => int.to_string(),
+                ASTNode::Call(function, ..) => format!("{}()", tree(function)),
+                node => panic!("unexpected {:?}", node),
+            }
+        }
 
-        assert_eq!(sections.len(), 1);
+        let parsed = |source| tree(&parse(&tokenise(source).unwrap()).unwrap());
+
+        // Precedence
+        assert_eq!(parsed("1 + 2 * 3"), "(1 + (2 * 3))");
+        assert_eq!(parsed("1 * 2 + 3"), "((1 * 2) + 3)");
+        assert_eq!(parsed("(1 + 2) * 3"), "((1 + 2) * 3)");
+        assert_eq!(parsed("a < 1 || b == 2 && c"), "((a < 1) || ((b == 2) && c))");
+
+        // Associativity
+        assert_eq!(parsed("10 - 4 - 3"), "((10 - 4) - 3)");
+        assert_eq!(parsed("2 ^ 3 ^ 2"), "(2 ^ (3 ^ 2))");
+        assert_eq!(parsed("a = b = 1"), "(a = (b = 1))");
+
+        // Unary operators
+        assert_eq!(parsed("-2 ^ 2"), "(-(2 ^ 2))");
+        assert_eq!(parsed("-2 * 3"), "((-2) * 3)");
+        assert_eq!(parsed("!a && b"), "((!a) && b)");
+        assert_eq!(parsed("!!a"), "(!(!a))");
+        assert_eq!(parsed("1 - -2"), "(1 - (-2))");
+
+        // Pipes bind looser than everything but assignment, and chain to the left
+        assert_eq!(parsed("a + 1 | f | g()"), "(((a + 1) | f) | g())");
+        assert_eq!(parsed("ls() |e grep() | wc() == 1"), "((ls() |e grep()) | (wc() == 1))");
+        assert_eq!(parsed("x = a | f"), "(x = (a | f))");
     }
 
+    #[test]
+    pub fn test_parse_program_recovers() {
+        use super::*;
+
+        let program = parse_program("a = 1\nb = (1, 2) 3\nprint(a) ]\nif a {\n  c = (1 +)\n  d = 2 ~\n}\ne = 3");
+
+        let errors: Vec<_> = program.errors.iter().map(|err| err.span().unwrap().line).collect();
+        assert_eq!(errors, [2, 3, 5, 6]);
+
+        assert!(matches!(program.statements.as_slice(), [
+            _,
+            box_invalid,
+            _,
+            if_statement,
+            _,
+        ] if matches!(**box_invalid, ASTNode::Invalid(Span { line: 2, .. })) && matches!(**if_statement, ASTNode::If(..))));
+
+        let program = parse_program("ls(1, 2\nx = 1");
+        assert!(matches!(program.errors.as_slice(), [SyntaxError::BracketMismatch(Span { line: 1, column: 3, .. })]));
+        assert!(matches!(program.statements.as_slice(), [invalid] if matches!(**invalid, ASTNode::Invalid(_))));
+    }
 }
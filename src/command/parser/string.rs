@@ -0,0 +1,256 @@
+use std::ops::Range;
+
+use crate::command::parser::syntax_err::SyntaxError;
+use crate::command::parser::tokeniser::Span;
+
+/// A string literal with its prefix applied and its escapes decoded. The letter before the opening quote decides which
+/// of these it becomes.
+#[derive(Clone, Debug, PartialEq)]
+pub enum StringToken {
+    /// `'...'`
+    Text(String),
+    /// `r'...'` is taken exactly as written, and used as a regular expression
+    Regex(String),
+    /// `i'...'` is a key which matches regardless of case, as in `headers.i'content-type'`
+    Insensitive(String),
+    /// `b'...'` may hold bytes which aren't valid UTF-8, written as `\xNN`
+    Bytes(Vec<u8>),
+    /// `f'...'` is text interleaved with `{expr}`s, which are evaluated and converted to text
+    Format(Vec<StringPart>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum StringPart {
+    Text(String),
+    /// The source of an interpolated expression, and where it sits in the input
+    Expression(String, Span),
+}
+
+/// Decodes a string lexeme, which spans `span` of the input. Strings may be quoted with `'` or `"`, or with three of
+/// either to hold the quotes themselves. A line break straight after three quotes isn't part of the string, so that
+/// multi-line strings can start on their own line.
+pub fn decode(lexeme: &str, span: Span) -> Result<StringToken, SyntaxError> {
+    let literal = Literal { lexeme, span };
+
+    let prefix = lexeme.chars().next().filter(char::is_ascii_lowercase);
+    let start = prefix.map_or(0, char::len_utf8);
+    let quote = &lexeme[start..start + 1];
+    let quotes = if lexeme.len() - start >= 6 && lexeme[start..].starts_with(&quote.repeat(3)) { 3 } else { 1 };
+
+    let mut body = start + quotes..lexeme.len() - quotes;
+
+    if quotes == 3 {
+        body.start += ["\r\n", "\n"].iter().find(|newline| lexeme[body.clone()].starts_with(**newline)).map_or(0, |newline| newline.len());
+    }
+
+    match prefix {
+        None => literal.text(body).map(StringToken::Text),
+        Some('r') => Ok(StringToken::Regex(lexeme[body].to_owned())),
+        Some('i') => literal.text(body).map(StringToken::Insensitive),
+        Some('b') => literal.unescape(body, true).map(StringToken::Bytes),
+        Some('f') => literal.format(body).map(StringToken::Format),
+        Some(prefix) => Err(literal.error(format!("'{}' isn't a string prefix", prefix), 0..1)),
+    }
+}
+
+/// A string lexeme being decoded, so that errors can point at the part of it which is wrong
+struct Literal<'a> {
+    lexeme: &'a str,
+    span: Span,
+}
+
+impl Literal<'_> {
+    /// The span of `range` within the lexeme
+    fn span(&self, range: Range<usize>) -> Span {
+        let before = &self.lexeme[..range.start];
+        let (line, column) = match before.rfind('\n') {
+            Some(newline) => (self.span.line + before.matches('\n').count() as i64, before[newline + 1..].chars().count() as i64 + 1),
+            None => (self.span.line, self.span.column + before.chars().count() as i64),
+        };
+
+        Span { start: self.span.start + range.start, end: self.span.start + range.end, line, column }
+    }
+
+    fn error(&self, reason: String, range: Range<usize>) -> SyntaxError {
+        SyntaxError::InvalidString(reason, self.span(range))
+    }
+
+    fn text(&self, range: Range<usize>) -> Result<String, SyntaxError> {
+        // Only byte strings may contain escapes which aren't valid UTF-8, so the text is always valid here
+        self.unescape(range, false).map(|text| String::from_utf8(text).expect("escapes outside of byte strings are valid UTF-8"))
+    }
+
+    /// Decodes the escapes in `range`. `\xNN` escapes above `\x7f` are only allowed in byte strings, where they stand for
+    /// a single byte rather than a character.
+    fn unescape(&self, range: Range<usize>, bytes: bool) -> Result<Vec<u8>, SyntaxError> {
+        let mut decoded = Vec::with_capacity(range.len());
+        let mut a = range.start;
+
+        while a < range.end {
+            let rest = &self.lexeme[a..range.end];
+            let Some(escape) = rest.strip_prefix('\\') else {
+                let char = rest.chars().next().unwrap();
+                decoded.extend_from_slice(char.encode_utf8(&mut [0; 4]).as_bytes());
+                a += char.len_utf8();
+                continue;
+            };
+
+            let (len, char) = match escape.chars().next() {
+                Some('n') => (1, '\n'),
+                Some('r') => (1, '\r'),
+                Some('t') => (1, '\t'),
+                Some('0') => (1, '\0'),
+                Some(char @ ('\\' | '\'' | '"' | '{' | '}')) => (1, char),
+                Some('x') => {
+                    let byte = escape.get(1..3)
+                        .filter(|digits| digits.chars().all(|c| c.is_ascii_hexdigit()))
+                        .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+                        .ok_or_else(|| self.error("'\\x' must be followed by two hexadecimal digits".to_owned(), a..a + 2))?;
+
+                    if bytes {
+                        decoded.push(byte);
+                        a += 4;
+                        continue;
+                    } else if byte > 0x7f {
+                        return Err(self.error("escapes above '\\x7f' are only allowed in b'' strings; use '\\u{...}' for characters".to_owned(), a..a + 4));
+                    }
+
+                    (3, byte as char)
+                }
+                Some('u') => {
+                    let end = escape.find('}').filter(|_| escape[1..].starts_with('{'));
+                    let char = end
+                        .and_then(|end| u32::from_str_radix(&escape[2..end], 16).ok())
+                        .and_then(char::from_u32)
+                        .ok_or_else(|| self.error("'\\u' must be followed by a character code in braces, as in '\\u{e9}'".to_owned(), a..a + 2))?;
+
+                    (end.unwrap() + 1, char)
+                }
+                Some(char) => return Err(self.error(format!("'\\{}' isn't an escape", char), a..a + 1 + char.len_utf8())),
+                None => return Err(self.error("a '\\' must be followed by the character it escapes".to_owned(), a..a + 1)),
+            };
+
+            decoded.extend_from_slice(char.encode_utf8(&mut [0; 4]).as_bytes());
+            a += 1 + len;
+        }
+
+        Ok(decoded)
+    }
+
+    /// Splits the body of an f-string into text and the expressions within it. Braces are written doubled, as in `{{`,
+    /// or escaped to stand for themselves.
+    fn format(&self, range: Range<usize>) -> Result<Vec<StringPart>, SyntaxError> {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut segment = range.start;
+        let mut a = range.start;
+
+        while a < range.end {
+            let rest = &self.lexeme[a..range.end];
+
+            if let Some(escape) = rest.strip_prefix('\\') {
+                // Escapes are decoded along with the rest of the text
+                a += 1 + escape.chars().next().map_or(0, char::len_utf8);
+            } else if rest.starts_with("{{") || rest.starts_with("}}") {
+                text += &self.text(segment..a + 1)?;
+                a += 2;
+                segment = a;
+            } else if rest.starts_with('}') {
+                return Err(self.error("a '}' must be written '}}' to appear in an f-string".to_owned(), a..a + 1));
+            } else if let Some(expression) = rest.strip_prefix('{') {
+                text += &self.text(segment..a)?;
+
+                let len = expression_len(expression).ok_or_else(|| self.error("the '{' is never closed".to_owned(), a..a + 1))?;
+                let source = &expression[..len];
+
+                if source.trim().is_empty() {
+                    return Err(self.error("'{}' must contain an expression".to_owned(), a..a + len + 2));
+                }
+
+                if !text.is_empty() {
+                    parts.push(StringPart::Text(std::mem::take(&mut text)));
+                }
+
+                parts.push(StringPart::Expression(source.to_owned(), self.span(a + 1..a + 1 + len)));
+                a += len + 2;
+                segment = a;
+            } else {
+                a += rest.chars().next().map_or(1, char::len_utf8);
+            }
+        }
+
+        text += &self.text(segment..range.end)?;
+
+        if !text.is_empty() {
+            parts.push(StringPart::Text(text));
+        }
+
+        Ok(parts)
+    }
+}
+
+/// The length of an interpolated expression up to the `}` which closes it. Braces and strings within it are skipped over.
+fn expression_len(rest: &str) -> Option<usize> {
+    let mut depth = 0;
+    let mut quote = None;
+    let mut chars = rest.char_indices();
+
+    while let Some((a, char)) = chars.next() {
+        match (quote, char) {
+            (Some(_), '\\') => {
+                chars.next();
+            }
+            (Some(open), char) if char == open => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"') => quote = Some(char),
+            (None, '{') => depth += 1,
+            (None, '}') if depth == 0 => return Some(a),
+            (None, '}') => depth -= 1,
+            (None, _) => {}
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn decode(lexeme: &str) -> Result<StringToken, SyntaxError> {
+        super::decode(lexeme, Span { start: 10, end: 10 + lexeme.len(), line: 2, column: 5 })
+    }
+
+    #[test]
+    pub fn test_decode() {
+        assert_eq!(decode(r#"'a\tb\'\u{e9}\x41'"#), Ok(StringToken::Text("a\tb'éA".to_owned())));
+        assert_eq!(decode(r"r'\d+\.'"), Ok(StringToken::Regex(r"\d+\.".to_owned())));
+        assert_eq!(decode("i'Content-Type'"), Ok(StringToken::Insensitive("Content-Type".to_owned())));
+        assert_eq!(decode(r"b'\xff\x00é'"), Ok(StringToken::Bytes(vec![0xff, 0, 0xc3, 0xa9])));
+        assert_eq!(decode("'''\nit's\n'''"), Ok(StringToken::Text("it's\n".to_owned())));
+        assert_eq!(decode("\"\"\"\"\"\""), Ok(StringToken::Text(String::new())));
+
+        assert_eq!(decode("f'{{x}} = {x.y} {f(\"}\")}!'"), Ok(StringToken::Format(vec![
+            StringPart::Text("{x} = ".to_owned()),
+            StringPart::Expression("x.y".to_owned(), Span { start: 21, end: 24, line: 2, column: 16 }),
+            StringPart::Text(" ".to_owned()),
+            StringPart::Expression("f(\"}\")".to_owned(), Span { start: 27, end: 33, line: 2, column: 22 }),
+            StringPart::Text("!".to_owned()),
+        ])));
+    }
+
+    #[test]
+    pub fn test_decode_errors() {
+        let reason = |lexeme| match decode(lexeme) {
+            Err(SyntaxError::InvalidString(reason, span)) => (reason, span.start - 10, span.end - 10),
+            result => panic!("{:?}", result),
+        };
+
+        assert_eq!(reason("'a\\qb'"), ("'\\q' isn't an escape".to_owned(), 2, 4));
+        assert_eq!(reason("'\\xff'"), ("escapes above '\\x7f' are only allowed in b'' strings; use '\\u{...}' for characters".to_owned(), 1, 5));
+        assert_eq!(reason("x'a'"), ("'x' isn't a string prefix".to_owned(), 0, 1));
+        assert_eq!(reason("f'{a'"), ("the '{' is never closed".to_owned(), 2, 3));
+        assert_eq!(reason("f'a}'"), ("a '}' must be written '}}' to appear in an f-string".to_owned(), 3, 4));
+        assert_eq!(reason("f'{ }'"), ("'{}' must contain an expression".to_owned(), 2, 5));
+    }
+}
@@ -1,15 +1,52 @@
 use std::error::Error;
 use std::fmt::{Debug, Display, Formatter};
-use crate::command::parser::ASTNode;
+use crate::command::parser::Span;
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq)]
 pub enum SyntaxError {
-    BracketMismatch(i64, i64),
-    UnexpectedToken(String, i64, i64),
-    InvalidSyntax(i64, i64),
+    BracketMismatch(Span),
+    UnexpectedToken(String, Span),
+    InvalidSyntax(Span),
+    /// A string literal which can't be decoded, and why
+    InvalidString(String, Span),
     UnexpectedEOF(),
-    UnsupportedExpression(ASTNode),
-    NoValue(String)
+}
+
+impl SyntaxError {
+    /// The part of the source the error refers to, if it came from the parser
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            SyntaxError::BracketMismatch(span) | SyntaxError::UnexpectedToken(_, span) | SyntaxError::InvalidSyntax(span) | SyntaxError::InvalidString(_, span) => Some(*span),
+            _ => None,
+        }
+    }
+
+    /// Moves the error from a piece of source which starts at `outer` to where it sits in the whole input
+    pub fn within(self, outer: Span) -> SyntaxError {
+        match self {
+            SyntaxError::BracketMismatch(span) => SyntaxError::BracketMismatch(span.within(outer)),
+            SyntaxError::UnexpectedToken(lexeme, span) => SyntaxError::UnexpectedToken(lexeme, span.within(outer)),
+            SyntaxError::InvalidSyntax(span) => SyntaxError::InvalidSyntax(span.within(outer)),
+            SyntaxError::InvalidString(reason, span) => SyntaxError::InvalidString(reason, span.within(outer)),
+            SyntaxError::UnexpectedEOF() => SyntaxError::UnexpectedEOF(),
+        }
+    }
+
+    /// How far into the source the error was found. Running out of input is as far as it's possible to get.
+    pub fn position(&self) -> usize {
+        self.span().map_or(usize::MAX, |span| span.start)
+    }
+
+    /// A suggestion on how to fix the error, shown beneath the diagnostic
+    pub fn hint(&self) -> Option<&'static str> {
+        match self {
+            SyntaxError::BracketMismatch(_) => Some("every bracket must be closed by one of the same kind"),
+            SyntaxError::UnexpectedToken(..) => Some("text which isn't a name, number or operator must be quoted"),
+            SyntaxError::UnexpectedEOF() => Some("the input ended part way through an expression"),
+            SyntaxError::InvalidString(..) => Some("strings may be prefixed with r, i, b or f, and escape characters with \\n, \\t, \\x41 or \\u{e9}"),
+            SyntaxError::InvalidSyntax(_) => None,
+        }
+    }
 }
 
 impl Debug for SyntaxError {
@@ -21,12 +58,11 @@ impl Debug for SyntaxError {
 impl Display for SyntaxError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
-            SyntaxError::BracketMismatch(line, col) => write!(f, "SyntaxError: Bracket Mismatch at {}:{}", line, col),
-            SyntaxError::UnexpectedToken(lexeme, line, col) => write!(f, "SyntaxError: Unexpected Token '{}' at {}:{}", lexeme, line, col),
-            SyntaxError::InvalidSyntax(line, col) => write!(f, "SyntaxError: Invalid Syntax at {}:{}", line, col),
+            SyntaxError::BracketMismatch(span) => write!(f, "SyntaxError: Bracket Mismatch at {}:{}", span.line, span.column),
+            SyntaxError::UnexpectedToken(lexeme, span) => write!(f, "SyntaxError: Unexpected Token '{}' at {}:{}", lexeme, span.line, span.column),
+            SyntaxError::InvalidSyntax(span) => write!(f, "SyntaxError: Invalid Syntax at {}:{}", span.line, span.column),
+            SyntaxError::InvalidString(reason, span) => write!(f, "SyntaxError: Invalid String at {}:{}: {}", span.line, span.column, reason),
             SyntaxError::UnexpectedEOF() => write!(f, "SyntaxError: Unexpected EOF"),
-            SyntaxError::UnsupportedExpression(node) => write!(f, "SyntaxError: Unsupported Expression: {:?}", node),
-            SyntaxError::NoValue(val) => write!(f, "SyntaxError: Value '{}' does not exist in scope.", val)
         }
     }
 }
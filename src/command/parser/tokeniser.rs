@@ -1,6 +1,6 @@
 use std::fmt::{Debug, Display, Formatter};
 
-use crate::command::parser::matchers::Matcher;
+use crate::command::parser::string::{self, StringToken};
 use crate::command::parser::syntax_err::SyntaxError;
 
 #[derive(Copy, Clone, Debug)]
@@ -34,16 +34,44 @@ pub enum OperatorType {
 impl OperatorType {
     pub fn precedence(&self) -> i8 {
         match self {
-            OperatorType::Pipe(_) => 0,
-            OperatorType::Or => 1,
-            OperatorType::And => 2,
-            OperatorType::Equal | OperatorType::NotEqual => 3,
-            OperatorType::GreaterThan | OperatorType::LessThan | OperatorType::GreaterThanOrEqual | OperatorType::LessThanOrEqual => 4,
-            OperatorType::Add | OperatorType::Subtract => 5,
-            OperatorType::Multiply | OperatorType::Divide | OperatorType::Modulo => 6,
-            OperatorType::Exponent => 7,
-            OperatorType::Not => 8,
-            OperatorType::Assign => 9,
+            OperatorType::Assign => 0,
+            OperatorType::Pipe(_) => 1,
+            OperatorType::Or => 2,
+            OperatorType::And => 3,
+            OperatorType::Equal | OperatorType::NotEqual => 4,
+            OperatorType::GreaterThan | OperatorType::LessThan | OperatorType::GreaterThanOrEqual | OperatorType::LessThanOrEqual => 5,
+            OperatorType::Add | OperatorType::Subtract => 6,
+            OperatorType::Multiply | OperatorType::Divide | OperatorType::Modulo => 7,
+            OperatorType::Exponent => 8,
+            OperatorType::Not => 9,
+        }
+    }
+
+    pub fn is_right_associative(&self) -> bool {
+        matches!(self, OperatorType::Exponent | OperatorType::Assign)
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            OperatorType::Pipe(PipeType::Stdout) => "|",
+            OperatorType::Pipe(PipeType::Stderr) => "|e",
+            OperatorType::Pipe(PipeType::Both) => "|oe",
+            OperatorType::Add => "+",
+            OperatorType::Subtract => "-",
+            OperatorType::Multiply => "*",
+            OperatorType::Divide => "/",
+            OperatorType::Modulo => "%",
+            OperatorType::Exponent => "^",
+            OperatorType::Equal => "==",
+            OperatorType::NotEqual => "!=",
+            OperatorType::GreaterThan => ">",
+            OperatorType::LessThan => "<",
+            OperatorType::GreaterThanOrEqual => ">=",
+            OperatorType::LessThanOrEqual => "<=",
+            OperatorType::And => "&&",
+            OperatorType::Or => "||",
+            OperatorType::Not => "!",
+            OperatorType::Assign => "=",
         }
     }
 }
@@ -56,9 +84,11 @@ pub enum KeywordType {
     Function,
     Return,
     Import,
+    Try,
+    Catch,
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum BracketType {
     Parenthesis,
     Brace,
@@ -69,7 +99,8 @@ pub enum BracketType {
 #[derive(Clone, Debug)]
 pub enum TokenType {
     Symbol(String),
-    String(String),
+    String(StringToken),
+    Integer(i64),
     Number(f64),
     Boolean(bool),
     Operator(OperatorType),
@@ -80,6 +111,7 @@ pub enum TokenType {
     Semicolon,
     Comma,
     Dot,
+    Spread,
     Lambda,
     Whitespace(String),
     Comment(String),
@@ -94,11 +126,59 @@ pub struct Token {
     pub index: usize,
 }
 
+/// Where a token sits in the source. `start` and `end` are byte offsets, while `line` and `column` count from 1.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: i64,
+    pub column: i64,
+}
+
+impl Span {
+    /// The span of `len` bytes from `start` in `input`
+    pub fn at(input: &str, start: usize, len: usize) -> Span {
+        let line_start = input[..start].rfind('\n').map_or(0, |i| i + 1);
+
+        Span {
+            start,
+            end: start + len,
+            line: input[..start].matches('\n').count() as i64 + 1,
+            column: input[line_start..start].chars().count() as i64 + 1,
+        }
+    }
+}
+
+impl Span {
+    /// Moves a span found in a piece of source which starts at `outer`, such as an f-string's interpolation, to where it
+    /// sits in the whole input
+    pub fn within(self, outer: Span) -> Span {
+        Span {
+            start: outer.start + self.start,
+            end: outer.start + self.end,
+            line: outer.line + self.line - 1,
+            column: if self.line == 1 { outer.column + self.column - 1 } else { self.column },
+        }
+    }
+}
+
+impl Token {
+    pub fn span(&self) -> Span {
+        Span {
+            start: self.index,
+            end: self.index + self.lexeme.len(),
+            line: self.line,
+            column: self.column,
+        }
+    }
+}
+
 impl Display for Token {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}({})", match self.token_type {
             TokenType::Symbol(_) => "Symbol",
             TokenType::String(_) => "String",
+            TokenType::Integer(_) => "Integer",
             TokenType::Number(_) => "Number",
             TokenType::Boolean(_) => "Boolean",
             TokenType::Operator(_) => "Operator",
@@ -109,6 +189,7 @@ impl Display for Token {
             TokenType::Semicolon => "Semicolon",
             TokenType::Comma => "Comma",
             TokenType::Dot => "Dot",
+            TokenType::Spread => "Spread",
             TokenType::Lambda => "Lambda",
             TokenType::Whitespace(_) => "Whitespace",
             TokenType::Comment(_) => "Comment",
@@ -117,37 +198,455 @@ impl Display for Token {
 }
 
 pub fn tokenise(input: &str) -> Result<Vec<Token>, SyntaxError> {
+    let (tokens, errors) = tokenise_recovering(input);
+
+    match errors.into_iter().next() {
+        Some(err) => Err(err),
+        None => Ok(tokens)
+    }
+}
+
+/// Tokenises the whole input, skipping over any characters which don't begin a token rather than stopping at the first.
+pub fn tokenise_recovering(input: &str) -> (Vec<Token>, Vec<SyntaxError>) {
     let mut tokens = Vec::new();
+    let mut errors = Vec::new();
 
-    let matcher = Matcher::new();
-
-    let mut index: usize = 0;
-
-    while index < input.len() {
-        if let Some((lexeme, r#type)) = matcher.match_all(&input[index..]) {
-            match r#type {
-                TokenType::Whitespace(_) => {}
-                TokenType::Comment(_) => {}
-                _ => tokens.push(Token {
-                    token_type: r#type,
-                    lexeme: lexeme.to_owned(),
-                    column: input[..=index].split('\n').last().unwrap().len() as i64,
-                    line: input[..=index].split('\n').count() as i64,
-                    index,
+    for token in Tokens::new(input) {
+        match token {
+            Ok(token) => tokens.push(token),
+            Err(err) => errors.push(err),
+        }
+    }
+
+    (tokens, errors)
+}
+
+/// A position in the source, counting lines and columns from 1 as `Span` does
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Cursor {
+    index: usize,
+    line: i64,
+    column: i64,
+}
+
+impl Default for Cursor {
+    fn default() -> Self {
+        Self { index: 0, line: 1, column: 1 }
+    }
+}
+
+/// The state `Tokens` needs to start lexing part way through the input
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+struct Resume {
+    cursor: Cursor,
+    /// Whether the last token was a dot, after which `-1` is a negative index, as in `list.-1`
+    after_dot: bool,
+}
+
+/// Reads tokens from the input one at a time in a single pass, keeping track of the line and column as it goes.
+pub struct Tokens<'a> {
+    input: &'a str,
+    state: Resume,
+    /// The last point after a line break outside of any token. Lexing from there gives the same tokens as lexing from
+    /// the start, which is what lets `Lexer` pick up where it left off.
+    boundary: Resume,
+}
+
+impl<'a> Tokens<'a> {
+    pub fn new(input: &'a str) -> Self {
+        Self::resume(input, Resume::default())
+    }
+
+    fn resume(input: &'a str, state: Resume) -> Self {
+        let mut tokens = Self { input, state, boundary: state };
+
+        // Skip a `#!/usr/bin/env esh` line, so that scripts can be run directly
+        if state.cursor.index == 0 && input.starts_with("#!") {
+            tokens.advance(input.find('\n').unwrap_or(input.len()));
+        }
+
+        tokens
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.input[self.state.cursor.index..]
+    }
+
+    /// Moves `len` bytes further into the input
+    fn advance(&mut self, len: usize) {
+        let cursor = &mut self.state.cursor;
+
+        for char in self.input[cursor.index..cursor.index + len].chars() {
+            match char {
+                '\n' => {
+                    cursor.line += 1;
+                    cursor.column = 1;
+                }
+                _ => cursor.column += 1,
+            }
+        }
+
+        cursor.index += len;
+    }
+
+    /// Moves past whitespace and comments. Fails if a block comment is never closed.
+    fn skip_trivia(&mut self) -> Result<(), SyntaxError> {
+        loop {
+            let rest = self.rest();
+
+            if rest.starts_with(char::is_whitespace) {
+                let len = rest.find(|c: char| !c.is_whitespace()).unwrap_or(rest.len());
+                self.advance(len);
+
+                if rest[..len].contains('\n') {
+                    self.boundary = self.state;
+                }
+            } else if rest.starts_with("//") {
+                self.advance(rest.find('\n').unwrap_or(rest.len()));
+            } else if let Some(comment) = rest.strip_prefix("/*") {
+                match comment.find("*/") {
+                    Some(end) => self.advance(end + 4),
+                    None => {
+                        self.advance(rest.len());
+                        return Err(SyntaxError::UnexpectedEOF());
+                    }
+                }
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    /// The length and type of the token at the start of `rest`, or `None` if no token starts there
+    fn token(&self, rest: &str) -> Option<(usize, TokenType)> {
+        let mut chars = rest.chars();
+        let first = chars.next()?;
+        let second = chars.next();
+
+        Some(match (first, second) {
+            ('"' | '\'', _) => (string_len(rest)?, TokenType::String(StringToken::Text(String::new()))),
+            ('a'..='z', Some('"' | '\'')) => (1 + string_len(&rest[1..])?, TokenType::String(StringToken::Text(String::new()))),
+            ('-', Some('0'..='9')) if self.state.after_dot => match number(&rest[1..]) {
+                // A minus directly between a dot and an integer is part of a negative index, as in `list.-1`
+                Some((len, TokenType::Integer(int))) => (len + 1, TokenType::Integer(-int)),
+                _ => (1, TokenType::Operator(OperatorType::Subtract)),
+            },
+            ('0'..='9', _) => {
+                let symbol = symbol_len(rest);
+
+                // Names may start with digits, so whichever reading is longer wins, as in `2nd`
+                match number(rest) {
+                    Some((len, number)) if len >= symbol => (len, number),
+                    _ => (symbol, TokenType::Symbol(String::new())),
+                }
+            }
+            (char, _) if is_symbol_char(char) => {
+                let len = symbol_len(rest);
+
+                (len, match &rest[..len] {
+                    "true" => TokenType::Boolean(true),
+                    "false" => TokenType::Boolean(false),
+                    "if" => TokenType::Keyword(KeywordType::If),
+                    "else" => TokenType::Keyword(KeywordType::Else),
+                    "for" => TokenType::Keyword(KeywordType::For),
+                    "function" => TokenType::Keyword(KeywordType::Function),
+                    "return" => TokenType::Keyword(KeywordType::Return),
+                    "import" => TokenType::Keyword(KeywordType::Import),
+                    "try" => TokenType::Keyword(KeywordType::Try),
+                    "catch" => TokenType::Keyword(KeywordType::Catch),
+                    _ => TokenType::Symbol(String::new()),
                 })
-            };
+            }
+            ('(', _) => (1, TokenType::OpenBracket(BracketType::Parenthesis)),
+            ('{', _) => (1, TokenType::OpenBracket(BracketType::Brace)),
+            ('[', _) => (1, TokenType::OpenBracket(BracketType::Bracket)),
+            (')', _) => (1, TokenType::CloseBracket(BracketType::Parenthesis)),
+            ('}', _) => (1, TokenType::CloseBracket(BracketType::Brace)),
+            (']', _) => (1, TokenType::CloseBracket(BracketType::Bracket)),
+            (':', _) => (1, TokenType::Colon),
+            (';', _) => (1, TokenType::Semicolon),
+            (',', _) => (1, TokenType::Comma),
+            ('.', _) if rest.starts_with("...") => (3, TokenType::Spread),
+            ('.', _) => (1, TokenType::Dot),
+            ('-', Some('>')) => (2, TokenType::Lambda),
+            ('|', Some('|')) => (2, TokenType::Operator(OperatorType::Or)),
+            ('|', _) => pipe(rest),
+            ('&', Some('&')) => (2, TokenType::Operator(OperatorType::And)),
+            ('=', Some('=')) => (2, TokenType::Operator(OperatorType::Equal)),
+            ('!', Some('=')) => (2, TokenType::Operator(OperatorType::NotEqual)),
+            ('>', Some('=')) => (2, TokenType::Operator(OperatorType::GreaterThanOrEqual)),
+            ('<', Some('=')) => (2, TokenType::Operator(OperatorType::LessThanOrEqual)),
+            (char, _) => (1, TokenType::Operator(match char {
+                '+' => OperatorType::Add,
+                '-' => OperatorType::Subtract,
+                '*' => OperatorType::Multiply,
+                '/' => OperatorType::Divide,
+                '%' => OperatorType::Modulo,
+                '^' => OperatorType::Exponent,
+                '>' => OperatorType::GreaterThan,
+                '<' => OperatorType::LessThan,
+                '!' => OperatorType::Not,
+                '=' => OperatorType::Assign,
+                _ => return None,
+            })),
+        })
+    }
+}
 
-            index += lexeme.len();
-        } else {
-            dbg!(tokens, index);
+impl Iterator for Tokens<'_> {
+    type Item = Result<Token, SyntaxError>;
 
-            return Err(SyntaxError::UnexpectedToken(
-                input[..=index].split('\n').last().unwrap().split_whitespace().last().unwrap().to_owned(),
-                input[..=index].split('\n').last().unwrap().len() as i64,
-                input[..=index].split('\n').count() as i64,
-            ))
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Err(err) = self.skip_trivia() {
+            return Some(Err(err));
         }
+
+        let start = self.state.cursor;
+        let rest = self.rest();
+        let first = rest.chars().next()?;
+
+        let Some((len, token_type)) = self.token(rest) else {
+            // A string which is never closed takes up the rest of the input
+            if matches!((first, rest[first.len_utf8()..].chars().next()), ('"' | '\'', _) | ('a'..='z', Some('"' | '\''))) {
+                self.advance(rest.len());
+                return Some(Err(SyntaxError::UnexpectedEOF()));
+            }
+
+            self.advance(first.len_utf8());
+            return Some(Err(SyntaxError::UnexpectedToken(first.to_string(), Span { start: start.index, end: self.state.cursor.index, line: start.line, column: start.column })));
+        };
+
+        let lexeme = &rest[..len];
+        self.advance(len);
+        self.state.after_dot = matches!(token_type, TokenType::Dot);
+
+        Some(Ok(Token {
+            token_type: match token_type {
+                TokenType::Symbol(_) => TokenType::Symbol(lexeme.to_owned()),
+                TokenType::String(_) => match string::decode(lexeme, Span { start: start.index, end: self.state.cursor.index, line: start.line, column: start.column }) {
+                    Ok(string) => TokenType::String(string),
+                    Err(err) => return Some(Err(err)),
+                },
+                token_type => token_type,
+            },
+            lexeme: lexeme.to_owned(),
+            line: start.line,
+            column: start.column,
+            index: start.index,
+        }))
+    }
+}
+
+fn is_symbol_char(char: char) -> bool {
+    char.is_alphanumeric() || matches!(char, '@' | '#' | '$' | '_')
+}
+
+fn symbol_len(rest: &str) -> usize {
+    rest.find(|c: char| !is_symbol_char(c)).unwrap_or(rest.len())
+}
+
+/// The length of the quoted string at the start of `rest`, including its quotes, or `None` if it's never closed. Three
+/// quotes in a row open a string which only three more close.
+fn string_len(rest: &str) -> Option<usize> {
+    let quote = &rest[..1];
+    let close = if rest.starts_with(&quote.repeat(3)) { &rest[..3] } else { quote };
+    let mut chars = rest.char_indices().skip(close.len());
+
+    while let Some((a, char)) = chars.next() {
+        match char {
+            '\\' => {
+                chars.next();
+            }
+            _ if rest[a..].starts_with(close) => return Some(a + close.len()),
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// Reads a pipe, which may say which of a process's outputs it carries: `|` or `|o` for standard output, `|e` for
+/// standard error and `|oe` or `|eo` for both, in either case.
+fn pipe(rest: &str) -> (usize, TokenType) {
+    let suffix = &rest[1..symbol_len(&rest[1..]) + 1];
+
+    // Only a suffix standing on its own counts, so that `|echo` is a pipe into `echo`
+    let pipe = match suffix.to_ascii_lowercase().as_str() {
+        "o" => PipeType::Stdout,
+        "e" => PipeType::Stderr,
+        "oe" | "eo" => PipeType::Both,
+        _ => return (1, TokenType::Operator(OperatorType::Pipe(PipeType::Stdout))),
+    };
+
+    (1 + suffix.len(), TokenType::Operator(OperatorType::Pipe(pipe)))
+}
+
+/// Reads the number at the start of `rest`: an integer or decimal, in hexadecimal after `0x` or binary after `0b`,
+/// optionally followed by an exponent of ten, as in `1.5x3` for 1500. Negative numbers are formed by the unary minus
+/// operator, so that `1-2` is a subtraction.
+fn number(rest: &str) -> Option<(usize, TokenType)> {
+    let (radix, prefix) = match rest.get(..2) {
+        Some("0x") if rest[2..].starts_with(|c: char| c.is_ascii_hexdigit()) => (16, 2),
+        Some("0b") if rest[2..].starts_with(['0', '1']) => (2, 2),
+        _ => (10, 0),
+    };
+
+    let digits = |from: usize| rest[from..].find(|c: char| !c.is_digit(radix)).map_or(rest.len(), |len| from + len);
+
+    let whole = digits(prefix);
+
+    if whole == prefix {
+        return None;
+    }
+
+    // The fraction needs a digit after the dot, as `list.0.name` indexes
+    let fraction = match rest[whole..].strip_prefix('.') {
+        Some(fraction) if fraction.starts_with(|c: char| c.is_digit(radix)) => Some(digits(whole + 1)),
+        _ => None,
+    };
+
+    let mantissa_end = fraction.unwrap_or(whole);
+    let exponent = match rest[mantissa_end..].strip_prefix(['x', 'X']) {
+        Some(exponent) => {
+            let sign = usize::from(exponent.starts_with(['+', '-']));
+
+            match exponent[sign..].find(|c: char| !c.is_ascii_digit()).unwrap_or(exponent.len() - sign) {
+                0 => None,
+                len => Some(mantissa_end + 1 + sign + len),
+            }
+        }
+        None => None,
+    };
+
+    let end = exponent.unwrap_or(mantissa_end);
+
+    if fraction.is_none() && exponent.is_none() {
+        if let Ok(int) = i64::from_str_radix(&rest[prefix..whole], radix) {
+            return Some((end, TokenType::Integer(int)));
+        }
+    }
+
+    let mut value = rest[prefix..whole].chars().fold(0f64, |value, digit| value * radix as f64 + digit.to_digit(radix).unwrap() as f64);
+
+    if let Some(fraction) = fraction {
+        let mut scale = 1f64;
+
+        for digit in rest[whole + 1..fraction].chars() {
+            scale /= radix as f64;
+            value += digit.to_digit(radix).unwrap() as f64 * scale;
+        }
+    }
+
+    if let Some(exponent) = exponent {
+        value *= 10f64.powi(rest[mantissa_end + 1..exponent].parse().ok()?);
     }
 
-    Ok(tokens)
+    Some((end, TokenType::Number(value)))
+}
+
+/// Lexes text which arrives a piece at a time, such as the lines of a command typed at the prompt. Rather than lexing
+/// everything again, each piece is lexed along with whatever came after the last line break outside of a token, which
+/// gives the same tokens as lexing all of the text at once.
+#[derive(Debug, Default)]
+pub struct Lexer {
+    input: String,
+    tokens: Vec<Token>,
+    errors: Vec<SyntaxError>,
+    /// Where to start lexing from when more text arrives, along with how many tokens and errors precede it
+    resume: (Resume, usize, usize),
+}
+
+impl Lexer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds text to the end of the input
+    pub fn push(&mut self, text: &str) {
+        self.input.push_str(text);
+
+        let (state, tokens, errors) = self.resume;
+        self.tokens.truncate(tokens);
+        self.errors.truncate(errors);
+
+        let mut lexer = Tokens::resume(&self.input, state);
+
+        while let Some(token) = lexer.next() {
+            // The boundary only moves past whitespace, so it comes before the token just read
+            if lexer.boundary != self.resume.0 {
+                self.resume = (lexer.boundary, self.tokens.len(), self.errors.len());
+            }
+
+            match token {
+                Ok(token) => self.tokens.push(token),
+                Err(err) => self.errors.push(err),
+            }
+        }
+
+        if lexer.boundary != self.resume.0 {
+            self.resume = (lexer.boundary, self.tokens.len(), self.errors.len());
+        }
+    }
+
+    pub fn input(&self) -> &str {
+        &self.input
+    }
+
+    pub fn tokens(&self) -> &[Token] {
+        &self.tokens
+    }
+
+    /// The characters which couldn't be lexed, and whether a string or comment is left open at the end
+    pub fn errors(&self) -> &[SyntaxError] {
+        &self.errors
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn types(input: &str) -> Vec<String> {
+        tokenise(input).unwrap().iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    pub fn test_tokenise() {
+        assert_eq!(types("größe = 0x1F + 1.5x2 |e cat |echo"), [
+            "Symbol(größe)", "Operator(=)", "Integer(0x1F)", "Operator(+)", "Number(1.5x2)", "Operator(|e)", "Symbol(cat)", "Operator(|)", "Symbol(echo)",
+        ]);
+        assert_eq!(types("list.-1.-2 list.0.1 /* a */ 2nd /* b */ iffy if"), [
+            "Symbol(list)", "Dot(.)", "Integer(-1)", "Dot(.)", "Integer(-2)", "Symbol(list)", "Dot(.)", "Number(0.1)", "Symbol(2nd)", "Symbol(iffy)", "Keyword(if)",
+        ]);
+
+        let tokens = tokenise("'a\nb' + é\n  x").unwrap();
+        assert_eq!(tokens[2].span(), Span { start: 8, end: 10, line: 2, column: 6 });
+        assert_eq!(tokens[3].span(), Span { start: 13, end: 14, line: 3, column: 3 });
+
+        assert_eq!(types("'''it's''' f'{x}' '' \"\"\"\"\"\""), ["String('''it's''')", "String(f'{x}')", "String('')", "String(\"\"\"\"\"\")"]);
+        assert!(matches!(&tokenise(r"x = '\q'").unwrap_err(), SyntaxError::InvalidString(_, span) if span.start == 5));
+
+        assert!(matches!(tokenise("print('open"), Err(SyntaxError::UnexpectedEOF())));
+        assert!(matches!(tokenise("'''open ' "), Err(SyntaxError::UnexpectedEOF())));
+        assert!(matches!(tokenise("1 /* open"), Err(SyntaxError::UnexpectedEOF())));
+    }
+
+    #[test]
+    pub fn test_lexer_resumes() {
+        let input = "x = { a: 1,\n  b: 'two\nlines' }\n// done\nx.-1 -> 2";
+        let (tokens, errors) = tokenise_recovering(input);
+
+        // However the input is divided up, it lexes the same as all at once
+        for size in [1, 2, 5, 7] {
+            let mut lexer = Lexer::new();
+
+            for chunk in input.chars().collect::<Vec<_>>().chunks(size) {
+                lexer.push(&chunk.iter().collect::<String>());
+            }
+
+            assert_eq!(lexer.input(), input);
+            assert_eq!(lexer.tokens().iter().map(|i| (i.to_string(), i.span())).collect::<Vec<_>>(), tokens.iter().map(|i| (i.to_string(), i.span())).collect::<Vec<_>>());
+            assert_eq!(lexer.errors(), errors);
+        }
+    }
 }
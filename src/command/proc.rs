@@ -1,8 +1,19 @@
-use std::process;
+use std::os::unix::process::ExitStatusExt;
+use std::process::Stdio;
+
+use futures::channel::oneshot;
+use futures::StreamExt;
+use tokio::io::AsyncWriteExt;
+use tokio::process;
+
+use crate::command::parser::PipeType;
+use crate::command::stream::ByteStream;
 
 pub struct ChildProcess {
+    pub name: String,
     pub process: process::Child,
     pub options: ProcessOptions,
+    output: ByteStream,
 }
 
 #[derive(Debug, Clone, Default)]
@@ -12,3 +23,75 @@ pub struct ProcessOptions {
     pub strip_ansi: bool,
     pub resolve_names_to_executables: bool
 }
+
+impl ChildProcess {
+    /// Spawns `binary`, reading its standard input from `stdin` (or the terminal if there is none), and capturing the
+    /// output selected by `output` through an OS pipe. Streams which are still backed by an unread pipe are connected to
+    /// the child directly, otherwise their contents are copied in by a background task.
+    pub fn spawn(binary: &str, argv: &[String], stdin: Option<ByteStream>, output: PipeType, options: ProcessOptions) -> std::io::Result<Self> {
+        let (reader, writer) = std::io::pipe()?;
+        let mut command = process::Command::new(binary);
+
+        command.args(argv);
+
+        match output {
+            PipeType::Stdout => command.stdout(writer).stderr(Stdio::inherit()),
+            PipeType::Stderr => command.stdout(Stdio::inherit()).stderr(writer),
+            PipeType::Both => command.stdout(writer.try_clone()?).stderr(writer),
+        };
+
+        let feed = match stdin {
+            None => {
+                command.stdin(Stdio::inherit());
+                None
+            }
+            Some(stream) => match stream.take_pipe() {
+                Some(pipe) => {
+                    command.stdin(pipe);
+                    None
+                }
+                None => {
+                    command.stdin(Stdio::piped());
+                    Some(stream)
+                }
+            }
+        };
+
+        let mut process = command.spawn()?;
+
+        // The command holds on to our copies of the pipe's write end, which must be closed for the reader to see EOF.
+        drop(command);
+
+        if let (Some(mut stream), Some(mut child_stdin)) = (feed, process.stdin.take()) {
+            tokio::spawn(async move {
+                while let Some(chunk) = stream.next().await {
+                    if child_stdin.write_all(&chunk).await.is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+
+        Ok(ChildProcess {
+            name: binary.rsplit('/').next().unwrap_or(binary).to_owned(),
+            process,
+            options,
+            output: ByteStream::from_pipe(reader),
+        })
+    }
+
+    /// Hands the child's captured output over to a stream. The child is reaped in the background once it exits, and its
+    /// exit status passed on to the stream. Children killed by a signal report `128 + signal`, as POSIX shells do.
+    pub fn into_output(self) -> ByteStream {
+        let ChildProcess { name, mut process, output, .. } = self;
+        let (sender, receiver) = oneshot::channel();
+
+        tokio::spawn(async move {
+            if let Ok(status) = process.wait().await {
+                let _ = sender.send(status.code().or(status.signal().map(|signal| 128 + signal)).unwrap_or(1));
+            }
+        });
+
+        output.with_process(&name, receiver)
+    }
+}
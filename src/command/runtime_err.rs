@@ -0,0 +1,255 @@
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter};
+use std::io::ErrorKind;
+
+use crate::command::parser::{Diagnostic, Span, SyntaxError};
+use crate::command::value::{Dict, Value};
+
+/// Errors raised while a program runs, as opposed to while it's parsed. Each belongs to one of a handful of classes,
+/// given by `kind`, which is how scripts tell them apart when catching them.
+#[derive(Clone)]
+pub enum RuntimeError {
+    NoValue(String),
+    InvalidArgument(String, String),
+    NotCallable(String),
+    InvalidOperation(String),
+    DuplicateKey(String),
+    MissingKey(String),
+    IndexOutOfRange(i64, usize),
+    /// A process which exited unsuccessfully, along with its exit status
+    ExitError(String, i32),
+    IoError(String),
+    /// An operation on a file which failed, with the file's location, the class of failure and a description of it
+    FileError(String, ErrorKind, String),
+    SpawnError(String, String),
+    /// Input which couldn't be read as a data format, with the format, the byte offset it went wrong at and why
+    ParseError(String, usize, String),
+    ImportError(String, String),
+    ImportCycle(Vec<String>),
+    /// The user interrupted the command with `Ctrl-C`
+    Cancelled,
+    /// Whatever was reading standard output stopped, as with `esh script.esh | head`, so there's no point carrying on
+    OutputClosed,
+    Syntax(SyntaxError),
+    /// Records that the error passed through a call to the named function on its way out, building up a stack trace
+    InFunction(String, Box<RuntimeError>),
+    /// Records where in the source the error was raised, as the call or name which raised it
+    At(Span, Box<RuntimeError>),
+    /// An error in a module's source, with the module's path and source so that it can be shown against them
+    InModule(String, String, Box<RuntimeError>),
+    /// Carries a `return` statement's value up to the function it returns from
+    Return(Box<Value>),
+}
+
+impl RuntimeError {
+    /// The error itself, without the functions it passed through or where it was raised
+    pub fn root(&self) -> &RuntimeError {
+        match self {
+            RuntimeError::InFunction(_, err) | RuntimeError::At(_, err) | RuntimeError::InModule(_, _, err) => err.root(),
+            err => err,
+        }
+    }
+
+    /// Records that the error was raised by the code at `span`, unless it's known where it was raised already. Errors
+    /// which only carry control flow are left alone, so that they can still be told apart.
+    pub fn at(self, span: Span) -> RuntimeError {
+        match self {
+            err @ (RuntimeError::At(..) | RuntimeError::Syntax(_) | RuntimeError::Return(_) | RuntimeError::Cancelled | RuntimeError::OutputClosed) => err,
+            err => RuntimeError::At(span, Box::new(err)),
+        }
+    }
+
+    /// The error for a failed write to standard output. The reader having gone away ends the program rather than being
+    /// reported.
+    pub fn output(err: std::io::Error) -> RuntimeError {
+        match err.kind() {
+            ErrorKind::BrokenPipe => RuntimeError::OutputClosed,
+            _ => RuntimeError::IoError(err.to_string()),
+        }
+    }
+
+    /// The error with every record of where it was raised removed
+    #[cfg(test)]
+    pub fn unplaced(self) -> RuntimeError {
+        match self {
+            RuntimeError::At(_, err) => err.unplaced(),
+            RuntimeError::InFunction(function, err) => RuntimeError::InFunction(function, Box::new(err.unplaced())),
+            err => err,
+        }
+    }
+
+    /// The path and source of the module the error was found in, along with the error as it lies in that module
+    pub fn module(&self) -> Option<(&str, &str, &RuntimeError)> {
+        match self {
+            RuntimeError::InModule(path, source, err) => Some((path, source, err)),
+            RuntimeError::InFunction(_, err) | RuntimeError::At(_, err) => err.module(),
+            _ => None,
+        }
+    }
+
+    /// Where the error was raised in the code being run. An error raised inside a function is placed at the call, as the
+    /// function may have been defined in another file.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            RuntimeError::At(span, _) => Some(*span),
+            RuntimeError::Syntax(err) => err.span(),
+            _ => None,
+        }
+    }
+
+    /// The functions the error passed through, starting with the innermost
+    pub fn trace(&self) -> Vec<&str> {
+        match self {
+            RuntimeError::InFunction(function, err) => {
+                let mut trace = err.trace();
+                trace.push(function);
+                trace
+            }
+            RuntimeError::At(_, err) | RuntimeError::InModule(_, _, err) => err.trace(),
+            _ => vec![],
+        }
+    }
+
+    /// The class of the error
+    pub fn kind(&self) -> &'static str {
+        match self.root() {
+            RuntimeError::NoValue(_) => "NameError",
+            RuntimeError::InvalidArgument(..) | RuntimeError::NotCallable(_) | RuntimeError::InvalidOperation(_) => "TypeError",
+            RuntimeError::DuplicateKey(_) | RuntimeError::MissingKey(_) | RuntimeError::IndexOutOfRange(..) => "KeyError",
+            RuntimeError::ExitError(..) => "ExitError",
+            RuntimeError::IoError(_) | RuntimeError::FileError(..) | RuntimeError::OutputClosed => "IOError",
+            RuntimeError::SpawnError(..) => "SpawnError",
+            RuntimeError::ParseError(..) => "ParseError",
+            RuntimeError::ImportError(..) | RuntimeError::ImportCycle(_) => "ImportError",
+            RuntimeError::Cancelled => "Cancelled",
+            RuntimeError::Syntax(_) | RuntimeError::Return(_) => "SyntaxError",
+            RuntimeError::InFunction(..) | RuntimeError::At(..) | RuntimeError::InModule(..) => unreachable!(),
+        }
+    }
+
+    /// A description of the error, without its class
+    pub fn message(&self) -> String {
+        match self.root() {
+            RuntimeError::NoValue(val) => format!("Value '{}' does not exist in scope.", val),
+            RuntimeError::InvalidArgument(function, err) => format!("Invalid argument to '{}': {}", function, err),
+            RuntimeError::NotCallable(type_name) => format!("Value of type '{}' is not callable", type_name),
+            RuntimeError::InvalidOperation(err) => format!("Invalid operation: {}", err),
+            RuntimeError::DuplicateKey(key) => format!("Duplicate key '{}' in dict", key),
+            RuntimeError::MissingKey(key) => format!("Key '{}' does not exist", key),
+            RuntimeError::IndexOutOfRange(index, len) => format!("Index {} is out of range for a length of {}", index, len),
+            RuntimeError::ExitError(binary, status) => format!("'{}' exited with status {}", binary, status),
+            RuntimeError::IoError(err) => err.clone(),
+            RuntimeError::FileError(location, _, err) => format!("{}: {}", location, err),
+            RuntimeError::SpawnError(binary, err) => format!("Failed to spawn '{}': {}", binary, err),
+            RuntimeError::ParseError(format, offset, err) => format!("Invalid {} at byte {}: {}", format, offset, err),
+            RuntimeError::ImportError(module, err) => format!("Cannot import '{}': {}", module, err),
+            RuntimeError::ImportCycle(chain) => format!("Import cycle: {}", chain.join(" -> ")),
+            RuntimeError::Cancelled => "Interrupted".to_owned(),
+            RuntimeError::OutputClosed => "Standard output was closed".to_owned(),
+            RuntimeError::Syntax(err) => err.to_string().trim_start_matches("SyntaxError: ").to_owned(),
+            RuntimeError::Return(_) => "'return' outside of a function".to_owned(),
+            RuntimeError::InFunction(..) | RuntimeError::At(..) | RuntimeError::InModule(..) => unreachable!(),
+        }
+    }
+
+    /// The exit status the shell reports for the error, which is the process's own status if it exited unsuccessfully
+    pub fn status(&self) -> i32 {
+        match self.root() {
+            RuntimeError::ExitError(_, status) => *status,
+            RuntimeError::Cancelled => 130,
+            // As a shell reports a program killed by `SIGPIPE`
+            RuntimeError::OutputClosed => 141,
+            _ => 1,
+        }
+    }
+
+    /// The error as it's seen by a `catch` block
+    pub fn to_value(&self) -> Value {
+        let mut dict = Dict::from([
+            ("kind".to_owned(), Value::String(self.kind().to_owned())),
+            ("message".to_owned(), Value::String(self.message())),
+            ("trace".to_owned(), Value::List(self.trace().into_iter().map(|function| Value::String(function.to_owned())).collect())),
+        ]);
+
+        if let RuntimeError::ExitError(_, status) = self.root() {
+            dict.insert("status".to_owned(), Value::Integer(*status as i64));
+        }
+
+        // Scripts can tell why a file operation failed without picking apart the message
+        if let RuntimeError::FileError(location, reason, _) = self.root() {
+            dict.insert("path".to_owned(), Value::String(location.clone()));
+            dict.insert("reason".to_owned(), Value::String(reason_name(*reason).to_owned()));
+        }
+
+        if let RuntimeError::ParseError(_, offset, _) = self.root() {
+            dict.insert("offset".to_owned(), Value::Integer(*offset as i64));
+        }
+
+        Value::Dict(dict)
+    }
+}
+
+/// The name a `catch` block sees for the reason a file operation failed
+fn reason_name(reason: ErrorKind) -> &'static str {
+    match reason {
+        ErrorKind::NotFound => "not_found",
+        ErrorKind::AlreadyExists => "already_exists",
+        ErrorKind::PermissionDenied => "permission_denied",
+        ErrorKind::IsADirectory => "is_a_directory",
+        ErrorKind::NotADirectory => "not_a_directory",
+        ErrorKind::DirectoryNotEmpty => "directory_not_empty",
+        ErrorKind::InvalidInput => "invalid_input",
+        ErrorKind::InvalidData => "invalid_data",
+        ErrorKind::Unsupported => "unsupported",
+        _ => "other",
+    }
+}
+
+impl Diagnostic for RuntimeError {
+    fn span_in(&self, source: &str) -> Option<Span> {
+        match (self.module(), self.root()) {
+            (Some((_, _, err)), _) => err.span_in(source),
+            (None, RuntimeError::Syntax(err)) => err.span_in(source),
+            (None, _) => self.span(),
+        }
+    }
+
+    fn origin(&self) -> Option<(&str, &str)> {
+        self.module().map(|(path, source, _)| (source, path))
+    }
+
+    fn hint(&self) -> Option<&'static str> {
+        match self.root() {
+            RuntimeError::NoValue(_) => Some("names must be assigned before they're used, and text which isn't a name must be quoted"),
+            RuntimeError::NotCallable(_) => Some("only functions and programs can be called"),
+            RuntimeError::Syntax(err) => err.hint(),
+            _ => None,
+        }
+    }
+}
+
+impl From<SyntaxError> for RuntimeError {
+    fn from(err: SyntaxError) -> Self {
+        RuntimeError::Syntax(err)
+    }
+}
+
+impl Debug for RuntimeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(&self, f)
+    }
+}
+
+impl Display for RuntimeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.kind(), self.message())?;
+
+        for function in self.trace() {
+            write!(f, "\n  in {}", function)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Error for RuntimeError {}
@@ -0,0 +1,179 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter};
+use std::rc::{Rc, Weak};
+
+use crate::command::value::{Captured, Function, Value};
+
+struct Frame {
+    variables: HashMap<String, Value>,
+    parent: Option<Environment>,
+}
+
+/// A chain of lexical scopes. Cloning an environment yields another handle to the same scope, which is how lambdas
+/// capture the scope they were defined in.
+#[derive(Clone)]
+pub struct Environment {
+    frame: Rc<RefCell<Frame>>,
+}
+
+/// A handle to a scope which doesn't keep it alive. See [`Captured`].
+#[derive(Clone)]
+pub struct WeakEnvironment {
+    frame: Weak<RefCell<Frame>>,
+}
+
+impl Debug for WeakEnvironment {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "WeakEnvironment")
+    }
+}
+
+impl WeakEnvironment {
+    pub fn upgrade(&self) -> Option<Environment> {
+        self.frame.upgrade().map(|frame| Environment { frame })
+    }
+}
+
+impl Debug for Environment {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Environment")
+    }
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Environment::new()
+    }
+}
+
+impl Environment {
+    pub fn new() -> Self {
+        Environment {
+            frame: Rc::new(RefCell::new(Frame {
+                variables: HashMap::new(),
+                parent: None,
+            }))
+        }
+    }
+
+    /// Creates a new scope nested inside this one.
+    pub fn child(&self) -> Self {
+        Environment {
+            frame: Rc::new(RefCell::new(Frame {
+                variables: HashMap::new(),
+                parent: Some(self.clone()),
+            }))
+        }
+    }
+
+    pub fn downgrade(&self) -> WeakEnvironment {
+        WeakEnvironment { frame: Rc::downgrade(&self.frame) }
+    }
+
+    /// Looks a name up in this scope, then in each enclosing scope in turn.
+    pub fn get(&self, name: &str) -> Option<Value> {
+        let frame = self.frame.borrow();
+
+        match frame.variables.get(name) {
+            Some(value) => Some(self.take(value)),
+            None => frame.parent.as_ref().and_then(|parent| parent.get(name))
+        }
+    }
+
+    /// Binds a name in this scope, shadowing any binding in an enclosing scope.
+    pub fn define(&self, name: &str, value: Value) {
+        self.frame.borrow_mut().variables.insert(name.to_owned(), value);
+    }
+
+    /// Rebinds the nearest existing binding of a name, or defines it in this scope if there is none.
+    pub fn assign(&self, name: &str, value: Value) {
+        if !self.set_existing(name, &value) {
+            self.define(name, value);
+        }
+    }
+
+    /// Every name visible from this scope, including those of enclosing scopes.
+    pub fn names(&self) -> Vec<String> {
+        let frame = self.frame.borrow();
+        let mut names: Vec<String> = frame.variables.keys().cloned().collect();
+
+        if let Some(parent) = &frame.parent {
+            names.extend(parent.names());
+        }
+
+        names
+    }
+
+    /// The bindings made in this scope itself, excluding those of enclosing scopes.
+    pub fn bindings(&self) -> Vec<(String, Value)> {
+        self.frame.borrow().variables.iter()
+            .map(|(name, value)| (name.clone(), self.take(value)))
+            .collect()
+    }
+
+    /// Copies a value out of this scope. A function which captured this scope weakly gets a strong handle to it, so
+    /// the copy keeps working wherever it ends up.
+    fn take(&self, value: &Value) -> Value {
+        match value {
+            Value::Function(Function::Lambda(params, body, Captured::Weak(_), name)) => {
+                Value::Function(Function::Lambda(params.clone(), body.clone(), Captured::Strong(self.clone()), name.clone()))
+            }
+            value => value.clone()
+        }
+    }
+
+    fn set_existing(&self, name: &str, value: &Value) -> bool {
+        let mut frame = self.frame.borrow_mut();
+
+        if let Some(existing) = frame.variables.get_mut(name) {
+            *existing = value.clone();
+            return true;
+        }
+
+        match &frame.parent {
+            Some(parent) => parent.set_existing(name, value),
+            None => false
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::command::parser::ASTNode;
+
+    #[test]
+    pub fn test_scope_chain() {
+        let global = Environment::new();
+        global.define("a", Value::Integer(1));
+
+        let inner = global.child();
+        inner.define("b", Value::Integer(2));
+        inner.assign("a", Value::Integer(3));
+
+        assert!(matches!(global.get("a"), Some(Value::Integer(3))));
+        assert!(global.get("b").is_none());
+        assert!(matches!(inner.get("b"), Some(Value::Integer(2))));
+
+        inner.define("a", Value::Integer(4));
+        assert!(matches!(inner.get("a"), Some(Value::Integer(4))));
+        assert!(matches!(global.get("a"), Some(Value::Integer(3))));
+    }
+
+    #[test]
+    pub fn test_weak_capture() {
+        let scope = Environment::new();
+        let weak = scope.downgrade();
+        scope.define("f", Value::Function(Function::Lambda(Vec::new(), Box::new(ASTNode::Nothing), Captured::Weak(scope.downgrade()), None)));
+
+        let Some(f) = scope.get("f") else { panic!("f is defined") };
+        assert!(matches!(f, Value::Function(Function::Lambda(_, _, Captured::Strong(_), _))));
+
+        drop(scope);
+        assert!(weak.upgrade().is_some());
+
+        drop(f);
+        assert!(weak.upgrade().is_none());
+    }
+}
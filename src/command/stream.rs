@@ -0,0 +1,202 @@
+use std::fmt::{Debug, Formatter};
+use std::io::{self, PipeReader};
+use std::os::fd::OwnedFd;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use futures::channel::oneshot;
+use futures::future::Shared;
+use futures::{FutureExt, Stream, StreamExt};
+use tokio::io::{AsyncRead, ReadBuf};
+use tokio::net::unix::pipe::Receiver;
+
+const CHUNK_SIZE: usize = 8192;
+
+enum Source {
+    Buffer(Vec<u8>),
+    Reader(Pin<Box<dyn AsyncRead + Send>>),
+    /// The read end of an OS pipe which has not been read from yet, and can therefore be handed to another process as-is.
+    Pipe(PipeReader),
+}
+
+/// A lazily-read sequence of byte chunks, such as the output of a process. Clones share the same underlying source, so
+/// a chunk read through one handle is not seen by another.
+#[derive(Clone)]
+pub struct ByteStream {
+    source: Arc<Mutex<Source>>,
+    /// The name of the process writing to the stream, and its exit status once it exits
+    process: Option<(String, Shared<oneshot::Receiver<i32>>)>,
+    /// The error which cut the stream short, if reading from it failed
+    error: Arc<Mutex<Option<io::Error>>>,
+}
+
+impl Debug for ByteStream {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ByteStream")
+    }
+}
+
+impl Stream for ByteStream {
+    type Item = Vec<u8>;
+
+    /// A stream which can't be read from ends early, with the error kept for `error` to report
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut source = self.source.lock().unwrap();
+
+        if let Source::Pipe(_) = &*source {
+            let Source::Pipe(pipe) = std::mem::replace(&mut *source, Source::Buffer(vec![])) else { unreachable!() };
+
+            match Receiver::from_owned_fd(OwnedFd::from(pipe)) {
+                Ok(receiver) => *source = Source::Reader(Box::pin(receiver)),
+                Err(err) => *self.error.lock().unwrap() = Some(err),
+            }
+        }
+
+        match &mut *source {
+            Source::Buffer(buffer) if buffer.is_empty() => Poll::Ready(None),
+            Source::Buffer(buffer) => Poll::Ready(Some(std::mem::take(buffer))),
+            Source::Pipe(_) => Poll::Ready(None),
+            Source::Reader(reader) => {
+                let mut chunk = vec![0u8; CHUNK_SIZE];
+                let mut buf = ReadBuf::new(&mut chunk);
+
+                match reader.as_mut().poll_read(cx, &mut buf) {
+                    Poll::Pending => Poll::Pending,
+                    Poll::Ready(Ok(())) if buf.filled().is_empty() => Poll::Ready(None),
+                    Poll::Ready(Ok(())) => {
+                        let len = buf.filled().len();
+                        chunk.truncate(len);
+                        Poll::Ready(Some(chunk))
+                    }
+                    Poll::Ready(Err(err)) => {
+                        *source = Source::Buffer(vec![]);
+                        *self.error.lock().unwrap() = Some(err);
+                        Poll::Ready(None)
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl ByteStream {
+    pub async fn merge(mut self) -> Vec<u8> {
+        let mut vec = vec![];
+
+        while let Some(i) = self.next().await {
+            vec.extend(i)
+        }
+
+        vec
+    }
+
+    pub fn from_string(s: &str) -> Self {
+        ByteStream::from_bytes(s.as_bytes().to_vec())
+    }
+
+    pub fn from_bytes(bytes: Vec<u8>) -> Self {
+        ByteStream {
+            source: Arc::new(Mutex::new(Source::Buffer(bytes))),
+            process: None,
+            error: Default::default(),
+        }
+    }
+
+    pub fn from_reader<R: AsyncRead + Send + 'static>(reader: R) -> Self {
+        ByteStream {
+            source: Arc::new(Mutex::new(Source::Reader(Box::pin(reader)))),
+            process: None,
+            error: Default::default(),
+        }
+    }
+
+    pub fn from_pipe(pipe: PipeReader) -> Self {
+        ByteStream {
+            source: Arc::new(Mutex::new(Source::Pipe(pipe))),
+            process: None,
+            error: Default::default(),
+        }
+    }
+
+    /// Marks the stream as the output of the named process, whose exit status will be sent through `status`
+    pub fn with_process(self, name: &str, status: oneshot::Receiver<i32>) -> Self {
+        ByteStream {
+            process: Some((name.to_owned(), status.shared())),
+            ..self
+        }
+    }
+
+    /// Waits for the process writing to the stream to exit, returning its name and exit status. Streams which don't come
+    /// from a process have neither.
+    pub async fn exit_status(&self) -> Option<(String, i32)> {
+        let (name, status) = self.process.as_ref()?;
+        Some((name.clone(), status.clone().await.ok()?))
+    }
+
+    /// The error which stopped the stream from being read to the end, if any. Every clone reports it.
+    pub fn error(&self) -> Option<io::Error> {
+        self.error.lock().unwrap().as_ref().map(|err| io::Error::new(err.kind(), err.to_string()))
+    }
+
+    /// Takes the underlying OS pipe out of the stream if nothing has been read from it yet, leaving the stream empty.
+    pub fn take_pipe(&self) -> Option<PipeReader> {
+        let mut source = self.source.lock().unwrap();
+
+        match std::mem::replace(&mut *source, Source::Buffer(vec![])) {
+            Source::Pipe(pipe) => Some(pipe),
+            other => {
+                *source = other;
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    pub async fn test_reader_stream() {
+        let stream = ByteStream::from_reader(&b"hello world"[..]);
+        assert_eq!(stream.merge().await, b"hello world");
+    }
+
+    #[tokio::test]
+    pub async fn test_pipe_stream() {
+        use std::io::Write;
+
+        let (reader, mut writer) = std::io::pipe().unwrap();
+        writer.write_all(b"piped").unwrap();
+        drop(writer);
+
+        assert_eq!(ByteStream::from_pipe(reader).merge().await, b"piped");
+    }
+
+    #[tokio::test]
+    pub async fn test_read_error() {
+        struct Broken;
+
+        impl AsyncRead for Broken {
+            fn poll_read(self: Pin<&mut Self>, _: &mut Context<'_>, _: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+                Poll::Ready(Err(io::Error::other("the pipe broke")))
+            }
+        }
+
+        let stream = ByteStream::from_reader(Broken);
+        assert!(stream.error().is_none());
+        assert!(stream.clone().merge().await.is_empty());
+        assert_eq!(stream.error().map(|err| err.to_string()).as_deref(), Some("the pipe broke"));
+
+        let checked = crate::command::eval::check_exit(&stream).await;
+        assert!(matches!(checked, Err(crate::command::runtime_err::RuntimeError::IoError(ref msg)) if msg == "the pipe broke"));
+    }
+
+    #[tokio::test]
+    pub async fn test_clones_share_source() {
+        let stream = ByteStream::from_string("once");
+        assert_eq!(stream.clone().merge().await, b"once");
+        assert!(stream.merge().await.is_empty());
+    }
+}
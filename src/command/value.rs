@@ -0,0 +1,289 @@
+use std::fmt::{Debug, Display, Formatter};
+
+use chrono::{DateTime, Local};
+use indexmap::IndexMap;
+use regex::Regex;
+use serde::de::{EnumAccess, MapAccess, SeqAccess, VariantAccess, Visitor};
+use serde::ser::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::command::builtins::Builtin;
+use crate::command::location::Location;
+use crate::command::parser::ASTNode;
+use crate::command::scope::{Environment, WeakEnvironment};
+use crate::command::stream::ByteStream;
+
+pub type Dict = IndexMap<String, Value>;
+
+#[derive(Debug, Clone)]
+pub enum Function {
+    /// A lambda's parameters and body, along with the scope it was defined in and the name it was defined with, if any
+    Lambda(Vec<String>, Box<ASTNode>, Captured, Option<String>),
+    Builtin(Builtin),
+}
+
+/// The scope a lambda was defined in. A named function is bound in the very scope it captures, so that binding only
+/// holds the scope weakly, or the two would keep each other alive. Reading the binding back out of the scope hands
+/// over a strong handle instead.
+#[derive(Debug, Clone)]
+pub enum Captured {
+    Strong(Environment),
+    Weak(WeakEnvironment),
+}
+
+/// The result of evaluating any expression. Values are passed between the stages of a pipeline as-is, so that structure
+/// is never lost by flattening to text. Raw process output is represented by the `Stream` variant.
+#[derive(Debug, Clone)]
+pub enum Value {
+    Null,
+    Boolean(bool),
+    Integer(i64),
+    Float(f64),
+    String(String),
+    Bytes(Vec<u8>),
+    List(Vec<Value>),
+    Dict(Dict),
+    Date(DateTime<Local>),
+    Location(Location),
+    Regex(Regex),
+    Function(Function),
+    Stream(ByteStream),
+}
+
+impl Value {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Null => "null",
+            Value::Boolean(_) => "boolean",
+            Value::Integer(_) => "integer",
+            Value::Float(_) => "float",
+            Value::String(_) => "string",
+            Value::Bytes(_) => "bytes",
+            Value::List(_) => "list",
+            Value::Dict(_) => "dict",
+            Value::Date(_) => "date",
+            Value::Location(_) => "location",
+            Value::Regex(_) => "regex",
+            Value::Function(_) => "function",
+            Value::Stream(_) => "stream",
+        }
+    }
+
+    /// Converts the value into bytes suitable for the standard input of a process. Text is passed through unchanged,
+    /// while structured values are written in their printed form.
+    pub fn into_stream(self) -> ByteStream {
+        match self {
+            Value::Stream(stream) => stream,
+            Value::String(str) => ByteStream::from_string(&str),
+            Value::Bytes(bytes) => ByteStream::from_bytes(bytes),
+            Value::Null => ByteStream::from_bytes(vec![]),
+            value => ByteStream::from_string(&format!("{:#}\n", value)),
+        }
+    }
+
+    fn write_indented(&self, f: &mut Formatter<'_>, depth: usize) -> std::fmt::Result {
+        let pretty = f.alternate();
+        let indent = |f: &mut Formatter<'_>, depth: usize| if pretty {
+            write!(f, "\n{}", "  ".repeat(depth))
+        } else {
+            write!(f, " ")
+        };
+
+        match self {
+            Value::List(list) if list.is_empty() => write!(f, "{{}}"),
+            Value::Dict(dict) if dict.is_empty() => write!(f, "{{:}}"),
+            Value::List(list) => {
+                write!(f, "{{")?;
+                for (a, i) in list.iter().enumerate() {
+                    indent(f, depth + 1)?;
+                    i.write_indented(f, depth + 1)?;
+                    if a + 1 < list.len() {
+                        write!(f, ",")?;
+                    }
+                }
+                indent(f, depth)?;
+                write!(f, "}}")
+            }
+            Value::Dict(dict) => {
+                write!(f, "{{")?;
+                for (a, (key, value)) in dict.iter().enumerate() {
+                    indent(f, depth + 1)?;
+                    if is_identifier(key) {
+                        write!(f, "{}: ", key)?;
+                    } else {
+                        write!(f, "'{}': ", escape(key))?;
+                    }
+                    value.write_indented(f, depth + 1)?;
+                    if a + 1 < dict.len() {
+                        write!(f, ",")?;
+                    }
+                }
+                indent(f, depth)?;
+                write!(f, "}}")
+            }
+            Value::Null => write!(f, "null"),
+            Value::Boolean(bool) => write!(f, "{}", bool),
+            Value::Integer(int) => write!(f, "{}", int),
+            Value::Float(float) => write!(f, "{:?}", float),
+            Value::String(str) => write!(f, "'{}'", escape(str)),
+            Value::Bytes(bytes) => write!(f, "b'{}'", bytes.escape_ascii()),
+            Value::Date(date) => write!(f, "Date('{}')", date.format("%Y-%m-%d %H:%M:%S")),
+            Value::Location(location) => write!(f, "Location('{}')", escape(&location.to_string())),
+            Value::Regex(regex) => write!(f, "r'{}'", regex.as_str().replace('\'', "\\'")),
+            Value::Function(Function::Lambda(args, _, _, _)) => write!(f, "<function({})>", args.join("; ")),
+            Value::Function(Function::Builtin(builtin)) => write!(f, "<builtin {}>", builtin.name),
+            Value::Stream(_) => write!(f, "<stream>"),
+        }
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.write_indented(f, 0)
+    }
+}
+
+/// Values are written to data formats such as JSON as the nearest thing the format has. Dates become RFC 3339 strings,
+/// and locations and regexes the text they're written as. Functions and streams have no such form.
+impl Serialize for Value {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Value::Null => serializer.serialize_unit(),
+            Value::Boolean(bool) => serializer.serialize_bool(*bool),
+            Value::Integer(int) => serializer.serialize_i64(*int),
+            Value::Float(float) if float.is_finite() => serializer.serialize_f64(*float),
+            Value::Float(float) => Err(S::Error::custom(format!("{} has no serialised form", float))),
+            Value::String(str) => serializer.serialize_str(str),
+            Value::Bytes(bytes) => match std::str::from_utf8(bytes) {
+                Ok(str) => serializer.serialize_str(str),
+                Err(_) => Err(S::Error::custom("bytes which aren't valid UTF-8 have no serialised form")),
+            },
+            Value::List(list) => serializer.collect_seq(list),
+            Value::Dict(dict) => serializer.collect_map(dict),
+            Value::Date(date) => serializer.serialize_str(&date.to_rfc3339()),
+            Value::Location(location) => serializer.collect_str(location),
+            Value::Regex(regex) => serializer.serialize_str(regex.as_str()),
+            Value::Function(_) | Value::Stream(_) => Err(S::Error::custom(format!("{} values have no serialised form", self.type_name()))),
+        }
+    }
+}
+
+/// Reads any self-describing data format into values. Integers stay integers and floats stay floats, and integers too
+/// large for an `i64` are an error rather than being rounded. Dicts keep their keys in the order they're read, and keys
+/// which aren't strings, as YAML allows, are written as they're printed. Tags, which YAML has too, are dropped.
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
+struct ValueVisitor;
+
+impl<'de> Visitor<'de> for ValueVisitor {
+    type Value = Value;
+
+    fn expecting(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "any value")
+    }
+
+    fn visit_bool<E>(self, bool: bool) -> Result<Value, E> {
+        Ok(Value::Boolean(bool))
+    }
+
+    fn visit_i64<E>(self, int: i64) -> Result<Value, E> {
+        Ok(Value::Integer(int))
+    }
+
+    fn visit_u64<E: serde::de::Error>(self, int: u64) -> Result<Value, E> {
+        i64::try_from(int).map(Value::Integer).map_err(|_| E::custom(format!("integer {} is too large", int)))
+    }
+
+    fn visit_f64<E>(self, float: f64) -> Result<Value, E> {
+        Ok(Value::Float(float))
+    }
+
+    fn visit_str<E>(self, str: &str) -> Result<Value, E> {
+        Ok(Value::String(str.to_owned()))
+    }
+
+    fn visit_string<E>(self, str: String) -> Result<Value, E> {
+        Ok(Value::String(str))
+    }
+
+    fn visit_bytes<E>(self, bytes: &[u8]) -> Result<Value, E> {
+        Ok(Value::Bytes(bytes.to_vec()))
+    }
+
+    fn visit_unit<E>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_none<E>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        Value::deserialize(deserializer)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+        let mut list = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+
+        while let Some(value) = seq.next_element()? {
+            list.push(value);
+        }
+
+        Ok(Value::List(list))
+    }
+
+    fn visit_enum<A: EnumAccess<'de>>(self, data: A) -> Result<Value, A::Error> {
+        let (_, value): (String, _) = data.variant()?;
+        value.newtype_variant()
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+        let mut dict = Dict::new();
+
+        while let Some((key, value)) = map.next_entry::<Value, Value>()? {
+            match key {
+                Value::String(key) => dict.insert(key, value),
+                key => dict.insert(key.to_string(), value),
+            };
+        }
+
+        Ok(Value::Dict(dict))
+    }
+}
+
+fn is_identifier(key: &str) -> bool {
+    !key.is_empty() && key.chars().all(|c| c.is_alphanumeric() || c == '_') && !key.starts_with(|c: char| c.is_ascii_digit())
+}
+
+fn escape(str: &str) -> String {
+    str.replace('\\', "\\\\").replace('\'', "\\'")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn test_display_scalars() {
+        assert_eq!(Value::Integer(3).to_string(), "3");
+        assert_eq!(Value::Float(3.0).to_string(), "3.0");
+        assert_eq!(Value::String("it's".to_owned()).to_string(), "'it\\'s'");
+        assert_eq!(Value::Bytes(vec![b'a', 0]).to_string(), "b'a\\x00'");
+    }
+
+    #[test]
+    pub fn test_display_collections() {
+        let list = Value::List(vec![Value::String("Applications".to_owned()), Value::String("config".to_owned())]);
+        assert_eq!(list.to_string(), "{ 'Applications', 'config' }");
+
+        let mut dict = Dict::new();
+        dict.insert("name".to_owned(), Value::String("John Doe".to_owned()));
+        dict.insert("content-type".to_owned(), Value::Null);
+        assert_eq!(Value::Dict(dict.clone()).to_string(), "{ name: 'John Doe', 'content-type': null }");
+        assert_eq!(format!("{:#}", Value::Dict(dict)), "{\n  name: 'John Doe',\n  'content-type': null\n}");
+    }
+}
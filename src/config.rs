@@ -0,0 +1,211 @@
+use std::fmt::{Debug, Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::command::location::Location;
+
+/// The keys of an `esh.toml`, all of which are optional
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    prompt: Option<String>,
+    login_dir: Option<String>,
+    module_path: Option<Vec<String>>,
+}
+
+/// The shell's settings. Each is taken from the last config file to set it, so the user's file overrides the system's.
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// A template for the prompt, see `render_prompt`
+    pub prompt: String,
+    /// The directory the interactive shell starts in
+    pub login_dir: Option<String>,
+    /// The directories `import` searches for modules
+    pub module_path: Option<Vec<PathBuf>>,
+}
+
+#[derive(Clone)]
+pub struct ConfigError {
+    pub file: PathBuf,
+    /// The line and column of the mistake, or nothing if the file couldn't be read at all
+    pub position: Option<(usize, usize)>,
+    pub message: String,
+}
+
+impl Debug for ConfigError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(&self, f)
+    }
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ConfigError: {} at {}", self.message.trim_end(), self.file.display())?;
+
+        match self.position {
+            Some((line, column)) => write!(f, ":{}:{}", line, column),
+            None => Ok(())
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            prompt: "> ".to_owned(),
+            login_dir: None,
+            module_path: None,
+        }
+    }
+}
+
+/// The system's config file followed by the user's
+pub fn config_files() -> Vec<PathBuf> {
+    let mut files = vec![PathBuf::from("/etc/esh/esh.toml")];
+    files.extend(dirs::config_dir().map(|dir| dir.join("esh").join("esh.toml")));
+    files
+}
+
+impl Config {
+    /// Loads each of the config files in turn. Files which don't exist are skipped, while malformed ones are reported and
+    /// otherwise ignored, so that a mistake in one doesn't prevent the shell from starting.
+    pub fn load(files: &[PathBuf]) -> (Config, Vec<ConfigError>) {
+        let mut config = Config::default();
+        let mut errors = Vec::new();
+
+        for file in files {
+            let source = match std::fs::read_to_string(file) {
+                Ok(source) => source,
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(err) => {
+                    errors.push(ConfigError { file: file.clone(), position: None, message: err.to_string() });
+                    continue;
+                }
+            };
+
+            match parse(file, &source) {
+                Ok(parsed) => config.apply(parsed),
+                Err(err) => errors.push(err),
+            }
+        }
+
+        (config, errors)
+    }
+
+    fn apply(&mut self, file: ConfigFile) {
+        if let Some(prompt) = file.prompt {
+            self.prompt = prompt;
+        }
+
+        if let Some(login_dir) = file.login_dir {
+            self.login_dir = Some(login_dir);
+        }
+
+        if let Some(module_path) = file.module_path {
+            self.module_path = Some(module_path.iter().map(|dir| PathBuf::from(expand(dir, &[]))).collect());
+        }
+    }
+
+    /// The login directory with its placeholders filled in. It's given as a location, as they're printed, and has to be on
+    /// the local filesystem for the shell to start in it.
+    pub fn login_dir(&self) -> Option<PathBuf> {
+        self.login_dir.as_ref().and_then(|dir| Location::parse(&expand(dir, &[])).local_path())
+    }
+}
+
+fn parse(file: &Path, source: &str) -> Result<ConfigFile, ConfigError> {
+    toml::from_str(source).map_err(|err| {
+        let offset = err.span().map_or(0, |span| span.start);
+        let line_start = source[..offset].rfind('\n').map_or(0, |i| i + 1);
+
+        ConfigError {
+            file: file.to_owned(),
+            position: Some((source[..offset].matches('\n').count() + 1, source[line_start..offset].chars().count() + 1)),
+            message: err.message().to_owned(),
+        }
+    })
+}
+
+/// Fills in `{USER}` and `{HOME}`, as well as any other placeholders given. Unknown placeholders are left as they are.
+fn expand(template: &str, placeholders: &[(&str, String)]) -> String {
+    let user = std::env::var("USER").or_else(|_| std::env::var("LOGNAME")).unwrap_or_default();
+    let home = dirs::home_dir().map(|dir| dir.display().to_string()).unwrap_or_default();
+
+    placeholders.iter()
+        .chain(&[("USER", user), ("HOME", home)])
+        .fold(template.to_owned(), |template, (name, value)| template.replace(&format!("{{{}}}", name), value))
+}
+
+/// Fills in a prompt template. Besides `{USER}` and `{HOME}`, `{PWD}` is the current directory, `{STATUS}` the exit
+/// status of the last command and `{DURATION}` how long it took to run.
+pub fn render_prompt(template: &str, status: i32, duration: Duration) -> String {
+    let pwd = std::env::current_dir().map(|dir| Location::local(dir).to_string()).unwrap_or_default();
+
+    expand(template, &[
+        ("PWD", pwd),
+        ("STATUS", status.to_string()),
+        ("DURATION", format_duration(duration)),
+    ])
+}
+
+fn format_duration(duration: Duration) -> String {
+    match duration.as_secs() {
+        0 => format!("{}ms", duration.as_millis()),
+        1..60 => format!("{:.1}s", duration.as_secs_f64()),
+        secs => format!("{}m{}s", secs / 60, secs % 60),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn test_load_config() {
+        let dir = std::env::temp_dir().join(format!("esh-test-config-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let system = dir.join("system.toml");
+        let user = dir.join("user.toml");
+        let broken = dir.join("broken.toml");
+
+        std::fs::write(&system, "prompt = 'esh({PWD})> '\nlogin_dir = 'file:/home/{USER}'\n").unwrap();
+        std::fs::write(&user, "# Overrides the system's prompt\nprompt = '[{STATUS}] > '\n").unwrap();
+        std::fs::write(&broken, "prompt = 'ok'\n\nlogin_dir = 3\n").unwrap();
+
+        let (config, errors) = Config::load(&[system.clone(), user, dir.join("missing.toml")]);
+        assert!(errors.is_empty());
+        assert_eq!(config.prompt, "[{STATUS}] > ");
+        assert_eq!(config.login_dir.as_deref(), Some("file:/home/{USER}"));
+
+        let (config, errors) = Config::load(&[system, broken]);
+        assert_eq!(config.prompt, "esh({PWD})> ");
+        assert!(matches!(errors.as_slice(), [ConfigError { position: Some((3, 13)), .. }]));
+
+        // A file which exists but can't be read has no position to point at
+        let (_, errors) = Config::load(std::slice::from_ref(&dir));
+        assert!(matches!(errors.as_slice(), [ConfigError { position: None, .. }]));
+        assert!(errors[0].to_string().ends_with(&format!(" at {}", dir.display())));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    pub fn test_unknown_key() {
+        let err = parse(Path::new("esh.toml"), "prompt = '> '\npromt = '> '\n").unwrap_err();
+        assert_eq!(err.position, Some((2, 1)));
+        assert!(err.to_string().starts_with("ConfigError: unknown field `promt`"));
+    }
+
+    #[test]
+    pub fn test_render_prompt() {
+        assert_eq!(render_prompt("[{STATUS}] {DURATION} > ", 1, Duration::from_millis(1500)), "[1] 1.5s > ");
+        assert_eq!(render_prompt("{DURATION} {OTHER}", 0, Duration::from_millis(25)), "25ms {OTHER}");
+        assert_eq!(render_prompt("{DURATION}", 0, Duration::from_secs(125)), "2m5s");
+        assert!(render_prompt("esh({PWD})> ", 0, Duration::ZERO).starts_with("esh(file:/"));
+    }
+}
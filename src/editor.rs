@@ -0,0 +1,187 @@
+use rustyline::completion::{Completer, FilenameCompleter, Pair};
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::{Context, Helper};
+
+use crate::command::builtins;
+use crate::command::eval::{executables, index};
+use crate::command::parser::{self, Lexer, SyntaxError, TokenType};
+use crate::command::scope::Environment;
+use crate::command::value::Value;
+
+const KEYWORDS: &[&str] = &["if", "else", "for", "function", "return", "import", "try", "catch"];
+
+/// Completes names, dict keys and paths at the prompt, using the shell's global scope to find variables.
+pub struct EshHelper {
+    pub env: Environment,
+    files: FilenameCompleter,
+}
+
+impl EshHelper {
+    pub fn new(env: Environment) -> Self {
+        Self {
+            env,
+            files: FilenameCompleter::new(),
+        }
+    }
+
+    /// Completes the word before `pos`. Words inside strings or containing a `/` are paths, words containing a `.` are
+    /// keys of the dict they index, and anything else is a name: a keyword, a variable, a builtin or an executable.
+    pub fn candidates(&self, line: &str, pos: usize) -> (usize, Vec<Pair>) {
+        let start = line[..pos].char_indices().rev()
+            .find(|(_, c)| !(c.is_alphanumeric() || "@#$_.".contains(*c)))
+            .map_or(0, |(i, c)| i + c.len_utf8());
+        let word = &line[start..pos];
+        let in_string = line[..pos].matches('\'').count() % 2 == 1 || line[..pos].matches('"').count() % 2 == 1;
+
+        if in_string || word.contains('/') || line[..start].ends_with(['/', '~']) {
+            return self.files.complete_path(line, pos).unwrap_or((pos, vec![]));
+        }
+
+        if let Some((base, partial)) = word.rsplit_once('.') {
+            return (pos - partial.len(), self.keys(base, partial));
+        }
+
+        let mut names: Vec<String> = KEYWORDS.iter().map(|i| i.to_string())
+            .chain(self.env.names())
+            .chain(builtins::names().map(str::to_owned))
+            .filter(|name| name.starts_with(word))
+            .collect();
+
+        // Scanning the `PATH` is slow, so only do so once there's something to go on
+        if !word.is_empty() {
+            names.extend(executables(word));
+        }
+
+        names.sort();
+        names.dedup();
+
+        (start, names.into_iter().map(|name| Pair { display: name.clone(), replacement: name }).collect())
+    }
+
+    /// The keys of the value a chain of names like `config.user` refers to. Only variables and literal keys are followed,
+    /// so completing never runs any code.
+    fn keys(&self, base: &str, partial: &str) -> Vec<Pair> {
+        let mut parts = base.split('.');
+
+        let Some(mut value) = parts.next().and_then(|name| self.env.get(name)) else {
+            return vec![];
+        };
+
+        for key in parts {
+            let key = match key.parse::<i64>() {
+                Ok(position) => Value::Integer(position),
+                Err(_) => Value::String(key.to_owned()),
+            };
+
+            match index(value, key) {
+                Ok(next) => value = next,
+                Err(_) => return vec![],
+            }
+        }
+
+        let keys: Vec<String> = match value {
+            Value::Dict(dict) => dict.into_keys().collect(),
+            Value::List(list) => (0..list.len()).map(|i| i.to_string()).collect(),
+            _ => vec![],
+        };
+
+        keys.into_iter()
+            .filter(|key| key.starts_with(partial))
+            .map(|key| Pair { display: key.clone(), replacement: key })
+            .collect()
+    }
+}
+
+/// Whether the input can't be run yet because it leaves a bracket, string or comment open or ends in an operator, in
+/// which case the prompt carries on reading lines.
+pub fn is_incomplete(input: &str) -> bool {
+    let mut lexer = Lexer::new();
+    lexer.push(input);
+
+    is_unfinished(&lexer)
+}
+
+/// As `is_incomplete`, for input which has been lexed already. The prompt lexes each line as it's entered, rather than
+/// the whole command every time.
+pub fn is_unfinished(lexer: &Lexer) -> bool {
+    match lexer.errors() {
+        [] => {}
+        [.., SyntaxError::UnexpectedEOF()] => return true,
+        _ => return false,
+    }
+
+    let tokens = lexer.tokens();
+    let (partners, _) = parser::match_brackets(tokens);
+
+    let unclosed = tokens.iter().zip(partners)
+        .any(|(token, partner)| matches!(token.token_type, TokenType::OpenBracket(_)) && partner.is_none());
+
+    unclosed || matches!(parser::parse_statements(tokens), Err(SyntaxError::UnexpectedEOF()))
+}
+
+impl Completer for EshHelper {
+    type Candidate = Pair;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<Pair>)> {
+        Ok(self.candidates(line, pos))
+    }
+}
+
+impl Hinter for EshHelper {
+    type Hint = String;
+}
+
+impl Highlighter for EshHelper {}
+
+impl Validator for EshHelper {}
+
+impl Helper for EshHelper {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::command::value::Dict;
+
+    fn complete(helper: &EshHelper, line: &str) -> Vec<String> {
+        helper.candidates(line, line.len()).1.into_iter().map(|i| i.replacement).collect()
+    }
+
+    #[test]
+    pub fn test_complete() {
+        let env = Environment::new();
+        env.define("esh_config", Value::Dict(Dict::from([
+            ("user".to_owned(), Value::Dict(Dict::from([("name".to_owned(), Value::Null), ("nickname".to_owned(), Value::Null)]))),
+            ("colour".to_owned(), Value::Boolean(true)),
+        ])));
+        env.define("esh_counter", Value::Integer(1));
+
+        let helper = EshHelper::new(env);
+
+        assert_eq!(complete(&helper, "1 + esh_co"), vec!["esh_config", "esh_counter"]);
+        assert!(complete(&helper, "x | fla").contains(&"flat_map".to_owned()));
+        assert_eq!(complete(&helper, "print(esh_config.user.n"), vec!["name", "nickname"]);
+        assert_eq!(complete(&helper, "esh_config."), vec!["user", "colour"]);
+        assert!(complete(&helper, "ech").contains(&"echo".to_owned()));
+        assert!(complete(&helper, "fu").contains(&"function".to_owned()));
+
+        // Words may follow separators which take up more than one byte
+        assert_eq!(complete(&helper, "€esh_co"), vec!["esh_config", "esh_counter"]);
+        assert_eq!(complete(&helper, "“esh_config.c"), vec!["colour"]);
+        assert!(complete(&helper, "x —").contains(&"if".to_owned()));
+    }
+
+    #[test]
+    pub fn test_is_incomplete() {
+        assert!(is_incomplete("if x {"));
+        assert!(is_incomplete("map(i ->"));
+        assert!(is_incomplete("ls() |"));
+        assert!(is_incomplete("function f(a) {\n  print(a"));
+        assert!(!is_incomplete("if x { 1 }"));
+        assert!(!is_incomplete("1 + 2"));
+        assert!(!is_incomplete("print(1))"));
+        assert!(is_incomplete("print('two\n"));
+        assert!(is_incomplete("x = 1 /* note"));
+    }
+}
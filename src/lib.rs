@@ -0,0 +1,4 @@
+pub mod command;
+pub mod config;
+pub mod editor;
+pub mod shell;
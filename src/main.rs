@@ -1,5 +1,6 @@
-mod command;
-mod shell;
+use std::io::Read;
+
+use esh::shell;
 
 #[tokio::main]
 async fn main() {
@@ -12,5 +13,32 @@ async fn main() {
     //
     // println!("{:#?}", ast);
 
-    shell::shell_main().await;
+    let mut args = std::env::args().skip(1);
+
+    // The name is what diagnostics refer to the source as
+    let (source, name, args) = match args.next().as_deref() {
+        None => return shell::shell_main().await,
+        Some("-c") => match args.next() {
+            Some(cmd) => (Ok(cmd), "<command>".to_owned(), args.collect()),
+            None => {
+                eprintln!("Usage: esh [-c command | - | script] [args...]");
+                std::process::exit(2);
+            }
+        },
+        Some("-") => {
+            let mut source = String::new();
+            (std::io::stdin().read_to_string(&mut source).map(|_| source), "<stdin>".to_owned(), args.collect())
+        }
+        Some(script) => (std::fs::read_to_string(script), script.to_owned(), args.collect()),
+    };
+
+    let source = match source {
+        Ok(source) => source,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            std::process::exit(1);
+        }
+    };
+
+    std::process::exit(shell::run_script(&source, &name, args).await);
 }
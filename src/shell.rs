@@ -1,27 +1,201 @@
 use std::io::Write;
-use crate::command::eval::eval;
-use crate::command::parser;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use futures::StreamExt;
+use rustyline::error::ReadlineError;
+use rustyline::history::DefaultHistory;
+use rustyline::Editor;
+
+use crate::command::eval::{check_exit, eval};
+use crate::command::module;
+use crate::command::parser::{self, Lexer};
+use crate::command::runtime_err::RuntimeError;
+use crate::command::scope::Environment;
+use crate::command::value::Value;
+use crate::config::{config_files, render_prompt, Config};
+use crate::editor::{is_unfinished, EshHelper};
+
+/// Where the prompt's history is kept between sessions
+fn history_file() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("esh").join("history"))
+}
+
+/// Loads the config files, reporting any mistakes in them, and applies the settings which scripts share with the prompt
+fn load_config() -> Config {
+    let (config, errors) = Config::load(&config_files());
+
+    for err in errors {
+        eprintln!("{}", err);
+    }
+
+    if let Some(module_path) = &config.module_path {
+        module::set_search_path(module_path.clone());
+    }
+
+    config
+}
 
 pub async fn shell_main() {
+    let env = Environment::new();
+    let config = load_config();
+
+    if let Some(login_dir) = config.login_dir() {
+        if let Err(err) = std::env::set_current_dir(&login_dir) {
+            eprintln!("Error: Cannot change to login directory '{}': {}", login_dir.display(), err);
+        }
+    }
+
+    let mut status = 0;
+    let mut duration = Duration::ZERO;
+
+    let mut editor = match Editor::<EshHelper, DefaultHistory>::new() {
+        Ok(editor) => editor,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            return;
+        }
+    };
+
+    editor.set_helper(Some(EshHelper::new(env.clone())));
+
+    let history = history_file();
+
+    if let Some(history) = &history {
+        let _ = editor.load_history(history);
+    }
+
     loop {
-        std::io::stdout().write_all(b"> ").unwrap();
-        std::io::stdout().flush().unwrap();
-        let mut cmd = String::new();
-        if std::io::stdin().read_line(&mut cmd).is_ok() && !cmd.is_empty(){
-            if let Ok(tokens) = parser::tokenise(&cmd) {
-                if tokens.is_empty() {
-                    continue;
+        let mut lexer = Lexer::new();
+        let mut prompt = render_prompt(&config.prompt, status, duration);
+
+        // Carry on reading lines while brackets are left open, with `Ctrl-C` abandoning the command
+        let read = loop {
+            match editor.readline(&prompt) {
+                Ok(line) => {
+                    lexer.push(&line);
+                    lexer.push("\n");
+
+                    if !is_unfinished(&lexer) {
+                        break Ok(());
+                    }
+
+                    prompt = ". ".to_owned();
                 }
+                Err(err) => break Err(err),
+            }
+        };
+
+        match read {
+            Ok(()) => {}
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(err) => {
+                eprintln!("Error: {}", err);
+                break;
+            }
+        }
+
+        let cmd = lexer.input();
+
+        if cmd.trim().is_empty() {
+            continue;
+        }
+
+        let _ = editor.add_history_entry(cmd.trim_end());
+
+        if let Some(history) = &history {
+            let _ = std::fs::create_dir_all(history.parent().unwrap());
+            let _ = editor.append_history(history);
+        }
+
+        let start = Instant::now();
+        status = 0;
+
+        let program = parser::parse_program(cmd);
+
+        for err in &program.errors {
+            eprintln!("{}", parser::render(err, cmd, "<prompt>"));
+            status = 1;
+        }
 
-                if let Ok(ast) = parser::parse(&tokens) {
-                    // dbg!(&ast);
-                    let res = eval(ast, Default::default()).await;
-                    println!("{:#?}", res);
-                    continue;
+        // A command with mistakes in it isn't run at all, rather than up to the first
+        if program.errors.is_empty() {
+            for ast in program.statements {
+                // dbg!(&ast);
+                // `Ctrl-C` abandons the rest of the command, while the processes it started receive the signal themselves
+                let result = tokio::select! {
+                    result = async { print_value(eval(ast, env.clone(), Default::default()).await?).await } => result,
+                    _ = tokio::signal::ctrl_c() => Err(RuntimeError::Cancelled),
+                };
+
+                if let Err(err) = result {
+                    if !matches!(err.root(), RuntimeError::OutputClosed) {
+                        eprintln!("{}", parser::render(&err, cmd, "<prompt>"));
+                    }
+
+                    status = err.status();
+                    break;
                 }
             }
+        }
+
+        duration = start.elapsed();
+    }
+}
+
+/// Runs a whole script non-interactively, returning its exit status. Every syntax error in the script is reported
+/// before anything is run, whereas at runtime it stops at the first error, including a process exiting unsuccessfully.
+/// Statements are separated by semicolons or line breaks, and their results are written out just as they would be at
+/// the prompt. The script's arguments are available to it as the list `args`, and `name` is what diagnostics call it.
+/// Modules are imported from the search path set in the config files, but the script stays in the current directory.
+pub async fn run_script(source: &str, name: &str, args: Vec<String>) -> i32 {
+    load_config();
+
+    let program = parser::parse_program(source);
+
+    if !program.errors.is_empty() {
+        for err in &program.errors {
+            eprintln!("{}", parser::render(err, source, name));
+        }
+
+        return 1;
+    }
+
+    let env = Environment::new();
+    env.define("args", Value::List(args.into_iter().map(Value::String).collect()));
+
+    for statement in program.statements {
+        let result = match eval(statement, env.clone(), Default::default()).await {
+            Ok(value) => print_value(value).await,
+            Err(err) => Err(err),
+        };
+
+        // Once standard output is closed the script stops, without anything to report
+        if let Err(err) = result {
+            if !matches!(err.root(), RuntimeError::OutputClosed) {
+                eprintln!("{}", parser::render(&err, source, name));
+            }
+
+            return err.status();
+        }
+    }
+
+    0
+}
+
+/// Writes a statement's result to standard output. Streams are copied through as they arrive, and nulls are left out.
+async fn print_value(value: Value) -> Result<(), RuntimeError> {
+    match value {
+        Value::Stream(mut stream) => {
+            while let Some(chunk) = stream.next().await {
+                let mut stdout = std::io::stdout().lock();
+                stdout.write_all(&chunk).and_then(|_| stdout.flush()).map_err(RuntimeError::output)?;
+            }
 
-            eprintln!("Error: {}", cmd);
+            check_exit(&stream).await
         }
+        Value::Null => Ok(()),
+        value => writeln!(std::io::stdout().lock(), "{:#}", value).map_err(RuntimeError::output),
     }
 }
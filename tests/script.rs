@@ -0,0 +1,123 @@
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+fn esh(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_esh"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    child.stdin.take().unwrap().write_all(stdin.as_bytes()).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+pub fn test_script_file() {
+    let script = std::env::temp_dir().join(format!("esh-test-script-{}.esh", std::process::id()));
+    std::fs::write(&script, "#!/usr/bin/env esh\ntotal = 0; for arg in args { total = total + len(arg) }\n\nprint(total)\necho(args.0)\n").unwrap();
+
+    let output = esh(&[script.to_str().unwrap(), "hello", "world"], "");
+    std::fs::remove_file(script).unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "10\nhello\n");
+}
+
+#[test]
+pub fn test_command_and_stdin() {
+    let output = esh(&["-c", "x = 2\nx * len(args) + len(args.0)", "abc", "d"], "");
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "7\n");
+
+    let output = esh(&["-"], "'piped' | len");
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "5\n");
+}
+
+#[test]
+pub fn test_exit_status() {
+    let output = esh(&["-c", "print('before'); esh_undefined_variable; print('after')"], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "before\n");
+    assert!(!output.stderr.is_empty());
+
+    assert_eq!(esh(&["esh-no-such-script.esh"], "").status.code(), Some(1));
+    assert_eq!(esh(&["-c", "1 +"], "").status.code(), Some(1));
+}
+
+#[test]
+pub fn test_diagnostics() {
+    let output = esh(&["-c", "x = 1\nprint(x ?)"], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains(" --> <command>:2:9\n  |\n2 | print(x ?)\n  |         ^"));
+
+    // Errors raised while running point at the name or call which raised them
+    let output = esh(&["-c", "undefined_thing + 1"], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("NameError: Value 'undefined_thing' does not exist in scope.\n --> <command>:1:1\n  |\n1 | undefined_thing + 1\n  | ^^^^^^^^^^^^^^^\n  = hint: "));
+
+    let output = esh(&["-c", "x = 1\nprint(x, len(x))"], "");
+    assert!(String::from_utf8_lossy(&output.stderr).contains(" --> <command>:2:10\n  |\n2 | print(x, len(x))\n  |          ^^^^^^"));
+}
+
+#[test]
+pub fn test_process_exit_status() {
+    let output = esh(&["-c", "print('before'); sh('-c', 'exit 3'); print('after')"], "");
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "before\n");
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("ExitError: 'sh' exited with status 3"));
+}
+
+#[test]
+pub fn test_closed_stdout() {
+    // Nobody reads the pipe the results are written to, as with `esh -c '...' | head -c0`, so the script stops quietly
+    let marker = std::env::temp_dir().join(format!("esh-test-closed-{}", std::process::id()));
+
+    for script in ["1; 'two'", "print('one'); 'two'", "sh('-c', 'echo one'); 'two'"] {
+        let (reader, writer) = std::io::pipe().unwrap();
+        drop(reader);
+
+        let output = Command::new(env!("CARGO_BIN_EXE_esh"))
+            .args(["-c", &format!("{}; sh('-c', 'touch {}')", script, marker.display())])
+            .stdout(writer)
+            .stderr(Stdio::piped())
+            .output()
+            .unwrap();
+
+        assert_eq!(output.status.code(), Some(141));
+        assert!(output.stderr.is_empty());
+        assert!(!marker.exists());
+    }
+}
+
+#[test]
+pub fn test_config_module_path() {
+    let dir = std::env::temp_dir().join(format!("esh-test-config-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("esh")).unwrap();
+    std::fs::create_dir_all(dir.join("lib")).unwrap();
+    std::fs::write(dir.join("esh/esh.toml"), format!("module_path = ['{}']\n", dir.join("lib").display())).unwrap();
+    std::fs::write(dir.join("lib/greeting.esh"), "function greet(name) { 'Hello, ' + name }").unwrap();
+
+    let script = dir.join("script.esh");
+    std::fs::write(&script, "import { greet } from 'greeting'\ngreet('script')").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_esh"))
+        .args(["-c", "import { greet } from 'greeting'; greet('command')"])
+        .env("XDG_CONFIG_HOME", &dir)
+        .output()
+        .unwrap();
+
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "'Hello, command'\n");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_esh"))
+        .arg(&script)
+        .env("XDG_CONFIG_HOME", &dir)
+        .output()
+        .unwrap();
+
+    std::fs::remove_dir_all(dir).unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "'Hello, script'\n");
+}